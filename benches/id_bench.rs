@@ -1,16 +1,60 @@
 use criterion::{criterion_group, criterion_main, Criterion};
 use idgenerator::*;
+use std::hint::black_box;
+use std::sync::Barrier;
+use std::time::{Duration, Instant};
+
+const THREADS: u64 = 64;
 
 fn id_generator(c: &mut Criterion) {
     let options = IdGeneratorOptions::new()
         .worker_id(1)
         .worker_id_bit_len(6)
         .seq_bit_len(12);
-    let _ = IdInstance::init(options).unwrap();
+    IdInstance::init(options).unwrap();
     let mut group = c.benchmark_group("id-generator");
-    group.bench_function("id", |b| b.iter(|| IdInstance::next_id()));
+    group.bench_function("id", |b| b.iter(IdInstance::next_id));
+    group.finish();
+}
+
+/// Generate `iters` ids in total from `THREADS` threads at the same time
+fn contend<F: Fn() -> i64 + Sync>(iters: u64, next_id: F) -> Duration {
+    let next_id = &next_id;
+    let barrier = &Barrier::new(THREADS as usize + 1);
+    let start = std::thread::scope(|s| {
+        for thread in 0..THREADS {
+            // The first thread takes the remainder, so exactly `iters` ids are generated
+            let count = iters / THREADS + if thread == 0 { iters % THREADS } else { 0 };
+            s.spawn(move || {
+                barrier.wait();
+                for _ in 0..count {
+                    black_box(next_id());
+                }
+            });
+        }
+        // Start the clock once all the threads are spawned, the scope returns after they are joined
+        barrier.wait();
+        Instant::now()
+    });
+    start.elapsed()
+}
+
+fn id_generator_contention(c: &mut Criterion) {
+    let options = IdGeneratorOptions::new()
+        .worker_id(1)
+        .worker_id_bit_len(6)
+        .seq_bit_len(12);
+    IdInstance::init(options.clone()).unwrap();
+    let generator = AtomicIdGenerator::new(options).unwrap();
+    let mut group = c.benchmark_group("id-generator-contention");
+    group.bench_function("mutex", |b| {
+        b.iter_custom(|iters| contend(iters, IdInstance::next_id))
+    });
+    group.bench_function("atomic", |b| {
+        b.iter_custom(|iters| contend(iters, || generator.next_id()))
+    });
     group.finish();
 }
 
-criterion_group!(benches, id_generator);
+criterion_group!(benches, id_generator, id_generator_contention);
 criterion_main!(benches);
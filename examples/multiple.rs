@@ -13,7 +13,7 @@ fn main() -> Result<(), OptionError> {
 
     // Initialize the id vec generator instance with the options.
     // Other options not set will be given the default value.
-    let _ = IdVecInstance::init(options)?;
+    IdVecInstance::init(options)?;
 
     // Get the options from the id vec generator instance of the index 0.
     let options = IdVecInstance::get_options(0).unwrap();
@@ -30,9 +30,9 @@ fn main() -> Result<(), OptionError> {
     // If new options are not compatible with the old options, it will return an error.
     //
     // Set index 0.
-    let _ = IdVecInstance::set_options(0, options.clone())?;
+    IdVecInstance::set_options(0, options.clone())?;
     // Set index 1.
-    let _ = IdVecInstance::set_options(1, options)?;
+    IdVecInstance::set_options(1, options)?;
 
     // Get the option from the id generator instance to see what have change and what remains the same as you set first time.
     let options = IdVecInstance::get_options(0).unwrap();
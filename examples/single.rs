@@ -9,7 +9,7 @@ fn main() -> Result<(), OptionError> {
 
     // Initialize the id generator instance with the option.
     // Other options not set will be given the default value.
-    let _ = IdInstance::init(options)?;
+    IdInstance::init(options)?;
 
     // Get the option from the id generator instance.
     let options = IdInstance::get_options();
@@ -20,7 +20,7 @@ fn main() -> Result<(), OptionError> {
     // Use `set_options` will only change the options you have set.
     // Other options will not change if not set.
     // If new options are not compatible with the old options, it will return an error.
-    let _ = IdInstance::set_options(options)?;
+    IdInstance::set_options(options)?;
 
     // Get the option from the id generator instance to see what have change and what remains the same as you set first time.
     let options = IdInstance::get_options();
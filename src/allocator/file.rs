@@ -0,0 +1,88 @@
+//! # File lock allocator
+//!
+//! Allocate worker ids among the processes on the same host, each holding an advisory lock on
+//! the lock file of its worker id in a shared directory.
+
+use std::fs::{self, File, OpenOptions, TryLockError};
+use std::io::Write;
+use std::path::PathBuf;
+
+use super::{max_worker_id, WorkerIdAllocator, WorkerIdLease};
+use crate::AllocateError;
+
+/// Allocate worker ids by locking files in a shared directory
+///
+/// Each worker id has a lock file `worker-{id}.lock` in the directory.
+/// The lock is taken with `flock`-style advisory locking, and released when the lease is dropped
+/// or the process exits, so it suits multiple processes on the same host.
+#[derive(Debug, Clone)]
+pub struct FileLockAllocator {
+    dir: PathBuf,
+}
+
+impl FileLockAllocator {
+    pub fn new<P: Into<PathBuf>>(dir: P) -> Self {
+        FileLockAllocator { dir: dir.into() }
+    }
+}
+
+impl WorkerIdAllocator for FileLockAllocator {
+    type Lease = FileLockLease;
+
+    fn allocate(&self, worker_id_bit_len: u8) -> Result<FileLockLease, AllocateError> {
+        let max_worker_id = max_worker_id(worker_id_bit_len)?;
+        fs::create_dir_all(&self.dir).map_err(|e| AllocateError::Backend(e.to_string()))?;
+        for worker_id in 0..=max_worker_id {
+            let path = self.dir.join(format!("worker-{worker_id}.lock"));
+            let mut file = OpenOptions::new()
+                .create(true)
+                .truncate(false)
+                .write(true)
+                .open(&path)
+                .map_err(|e| AllocateError::Backend(e.to_string()))?;
+            match file.try_lock() {
+                Ok(()) => {
+                    // Record the owner for troubleshooting, the lock itself is what matters
+                    let _ = file
+                        .set_len(0)
+                        .and_then(|_| write!(file, "{}", std::process::id()));
+                    return Ok(FileLockLease {
+                        worker_id,
+                        path,
+                        file,
+                    });
+                }
+                Err(TryLockError::WouldBlock) => continue,
+                Err(TryLockError::Error(e)) => return Err(AllocateError::Backend(e.to_string())),
+            }
+        }
+        Err(AllocateError::Exhausted(max_worker_id))
+    }
+}
+
+/// Worker id held by locking its file, released when dropped
+#[derive(Debug)]
+pub struct FileLockLease {
+    worker_id: u32,
+    path: PathBuf,
+    file: File,
+}
+
+impl FileLockLease {
+    /// Path of the lock file
+    pub fn path(&self) -> &PathBuf {
+        &self.path
+    }
+}
+
+impl WorkerIdLease for FileLockLease {
+    fn worker_id(&self) -> u32 {
+        self.worker_id
+    }
+}
+
+impl Drop for FileLockLease {
+    fn drop(&mut self) {
+        let _ = self.file.unlock();
+    }
+}
@@ -0,0 +1,74 @@
+//! # Allocator
+//!
+//! Allocate worker ids for generators, so that no two running generators share the same worker id.
+//!
+//! - `WorkerIdAllocator`: claims a free worker id and returns a `WorkerIdLease` of it.
+//! - `WorkerIdLease`: holds the worker id until dropped.
+//! - `WorkerIdSource`: derives a worker id from deployment facts instead, e.g. the ordinal of a StatefulSet pod.
+//!
+//! Implementations:
+//!
+//! - `FileLockAllocator`: locks files in a shared directory, for multiple processes on the same host.
+//! - `SqlLeaseAllocator` (feature `sql`): leases rows of a database table with expiry, for a fleet of hosts,
+//!   with the statements run by a `SqlLeaseBackend`, e.g. `SqliteLeaseBackend` (feature `sqlite`).
+//! - `RedisLeaseAllocator` (feature `redis`): sets keys with expiry in Redis, for a fleet of hosts.
+
+mod file;
+pub(crate) mod source;
+
+#[cfg(feature = "redis")]
+mod redis;
+#[cfg(feature = "sql")]
+mod sql;
+#[cfg(feature = "sqlite")]
+mod sqlite;
+
+#[cfg(feature = "redis")]
+pub use self::redis::{RedisLease, RedisLeaseAllocator, RedisLeaseHeartbeat};
+pub use file::{FileLockAllocator, FileLockLease};
+pub use source::{hash_worker_id, ipv4_worker_id, ordinal_worker_id, WorkerIdSource};
+#[cfg(feature = "sql")]
+pub use sql::{SqlLease, SqlLeaseAllocator, SqlLeaseBackend, SqlLeaseHeartbeat};
+#[cfg(feature = "sqlite")]
+pub use sqlite::SqliteLeaseBackend;
+
+use std::fmt::Debug;
+
+use crate::generator::check_worker_id_bit_len;
+use crate::{AllocateError, IdGeneratorOptions};
+
+/// A worker id held by the owner of the lease, released when dropped
+///
+/// Leases with expiry are renewed periodically, e.g. by a `keep_alive` heartbeat.
+/// A failed renewal is retried on the next beat, as long as the lease has not expired.
+pub trait WorkerIdLease: Debug + Send {
+    /// The worker id held
+    fn worker_id(&self) -> u32;
+
+    /// Whether the worker id is still held
+    fn is_valid(&self) -> bool {
+        true
+    }
+
+    /// Set the worker id held into the options
+    fn apply(&self, options: IdGeneratorOptions) -> IdGeneratorOptions {
+        options.worker_id(self.worker_id())
+    }
+}
+
+/// Allocator of worker ids
+///
+/// Allocators shared by several hosts tell the holders of the worker ids apart by an owner,
+/// which should be unique among all the allocators sharing the backend, e.g. hostname and pid.
+pub trait WorkerIdAllocator {
+    type Lease: WorkerIdLease;
+
+    /// Claim the lowest free worker id in \[0, 2^worker_id_bit_len-1\]
+    fn allocate(&self, worker_id_bit_len: u8) -> Result<Self::Lease, AllocateError>;
+}
+
+/// The max worker id of the bit length, checked the same as the options
+pub(crate) fn max_worker_id(worker_id_bit_len: u8) -> Result<u32, AllocateError> {
+    check_worker_id_bit_len(worker_id_bit_len)?;
+    Ok((1 << worker_id_bit_len) - 1)
+}
@@ -0,0 +1,244 @@
+//! # Redis lease allocator
+//!
+//! Allocate worker ids among a fleet of hosts sharing a Redis server, each holding a key
+//! with expiry, with subscribers notified once the lease is lost.
+
+use std::fmt::{self, Debug};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::sync::{Arc, Weak};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use parking_lot::Mutex;
+use redis::{Client, Connection};
+
+use super::{max_worker_id, WorkerIdAllocator, WorkerIdLease};
+use crate::AllocateError;
+
+/// Extend the expiry of the key if it is still held by the owner
+const RENEW_SCRIPT: &str = "if redis.call('GET', KEYS[1]) == ARGV[1] then \
+    return redis.call('PEXPIRE', KEYS[1], ARGV[2]) else return 0 end";
+
+/// Delete the key if it is still held by the owner
+const RELEASE_SCRIPT: &str = "if redis.call('GET', KEYS[1]) == ARGV[1] then \
+    return redis.call('DEL', KEYS[1]) else return 0 end";
+
+fn backend(e: redis::RedisError) -> AllocateError {
+    AllocateError::Backend(e.to_string())
+}
+
+/// Allocate worker ids by setting keys with expiry in Redis, for a fleet of hosts sharing the server
+///
+/// Each worker id has a key `{key_prefix}{worker_id}` holding the owner, claimed with `SET NX PX`
+/// so it expires after the lease duration unless renewed. The worker ids of crashed owners are taken over
+/// once their keys expire.
+///
+/// Leases must be renewed before expiring, see `RedisLease::renew` and `RedisLease::keep_alive`.
+#[derive(Debug, Clone)]
+pub struct RedisLeaseAllocator {
+    client: Client,
+    key_prefix: String,
+    owner: String,
+    lease_duration: Duration,
+}
+
+impl RedisLeaseAllocator {
+    pub fn new<S: Into<String>>(client: Client, owner: S, lease_duration: Duration) -> Self {
+        RedisLeaseAllocator {
+            client,
+            key_prefix: "idgenerator:worker:".to_string(),
+            owner: owner.into(),
+            lease_duration,
+        }
+    }
+
+    /// Set the prefix of the keys, `idgenerator:worker:` by default
+    pub fn key_prefix<S: Into<String>>(mut self, key_prefix: S) -> Self {
+        self.key_prefix = key_prefix.into();
+        self
+    }
+
+    pub fn owner(&self) -> &str {
+        &self.owner
+    }
+}
+
+impl WorkerIdAllocator for RedisLeaseAllocator {
+    type Lease = RedisLease;
+
+    fn allocate(&self, worker_id_bit_len: u8) -> Result<RedisLease, AllocateError> {
+        let max_worker_id = max_worker_id(worker_id_bit_len)?;
+        let mut conn = self.client.get_connection().map_err(backend)?;
+        for worker_id in 0..=max_worker_id {
+            let key = format!("{}{worker_id}", self.key_prefix);
+            // The key expires no earlier on the server than the deadline measured before sending
+            let sent_at = Instant::now();
+            let claimed: Option<String> = redis::cmd("SET")
+                .arg(&key)
+                .arg(&self.owner)
+                .arg("NX")
+                .arg("PX")
+                .arg(self.lease_duration.as_millis() as u64)
+                .query(&mut conn)
+                .map_err(backend)?;
+            if claimed.is_some() {
+                return Ok(RedisLease {
+                    worker_id,
+                    key,
+                    owner: self.owner.clone(),
+                    lease_duration: self.lease_duration,
+                    conn: Mutex::new(conn),
+                    deadline: Mutex::new(sent_at + self.lease_duration),
+                    lost: AtomicBool::new(false),
+                    subscribers: Mutex::new(Vec::new()),
+                });
+            }
+        }
+        Err(AllocateError::Exhausted(max_worker_id))
+    }
+}
+
+/// Worker id leased by a key in Redis, deleted when dropped
+///
+/// The lease is valid until the lease duration after the last successful claim or renewal,
+/// measured from before the command is sent, so it turns invalid before the key can expire on the server.
+/// Share it with `CoreIdGenerator::set_lease` to stop generating ids once it is lost.
+pub struct RedisLease {
+    worker_id: u32,
+    key: String,
+    owner: String,
+    lease_duration: Duration,
+    conn: Mutex<Connection>,
+    deadline: Mutex<Instant>,
+    lost: AtomicBool,
+    subscribers: Mutex<Vec<Sender<u32>>>,
+}
+
+impl RedisLease {
+    /// The key holding the worker id
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+
+    /// Extend the lease by the lease duration, `false` if the lease is lost
+    pub fn renew(&self) -> Result<bool, AllocateError> {
+        if self.lost.load(Ordering::SeqCst) {
+            return Ok(false);
+        }
+        let sent_at = Instant::now();
+        let renewed: i64 = redis::cmd("EVAL")
+            .arg(RENEW_SCRIPT)
+            .arg(1)
+            .arg(&self.key)
+            .arg(&self.owner)
+            .arg(self.lease_duration.as_millis() as u64)
+            .query(&mut *self.conn.lock())
+            .map_err(backend)?;
+        if renewed == 0 {
+            self.set_lost();
+            return Ok(false);
+        }
+        *self.deadline.lock() = sent_at + self.lease_duration;
+        Ok(true)
+    }
+
+    /// Receive the worker id once the lease is found lost, by `renew` or the heartbeat of `keep_alive`
+    pub fn subscribe(&self) -> Receiver<u32> {
+        let (sender, receiver) = mpsc::channel();
+        let mut subscribers = self.subscribers.lock();
+        if self.lost.load(Ordering::SeqCst) {
+            let _ = sender.send(self.worker_id);
+        } else {
+            subscribers.push(sender);
+        }
+        receiver
+    }
+
+    /// Renew the lease in a background thread every `interval`, which should be well below the lease duration.
+    ///
+    /// The subscribers are notified once the lease is lost or expires before it could be renewed.
+    /// The heartbeat stops when dropped or when the lease is lost.
+    pub fn keep_alive(self: &Arc<Self>, interval: Duration) -> RedisLeaseHeartbeat {
+        let lease = Arc::downgrade(self);
+        let (stop, stopped) = mpsc::channel();
+        let handle = thread::spawn(move || {
+            while let Err(RecvTimeoutError::Timeout) = stopped.recv_timeout(interval) {
+                let lease = match Weak::upgrade(&lease) {
+                    Some(lease) => lease,
+                    None => break,
+                };
+                let _ = lease.renew();
+                if !lease.is_valid() {
+                    lease.set_lost();
+                    break;
+                }
+            }
+        });
+        RedisLeaseHeartbeat {
+            stop: Some(stop),
+            handle: Some(handle),
+        }
+    }
+
+    fn set_lost(&self) {
+        if !self.lost.swap(true, Ordering::SeqCst) {
+            for subscriber in self.subscribers.lock().drain(..) {
+                let _ = subscriber.send(self.worker_id);
+            }
+        }
+    }
+}
+
+impl Debug for RedisLease {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RedisLease")
+            .field("worker_id", &self.worker_id)
+            .field("key", &self.key)
+            .field("owner", &self.owner)
+            .field("lease_duration", &self.lease_duration)
+            .field("deadline", &self.deadline)
+            .field("lost", &self.lost)
+            .finish_non_exhaustive()
+    }
+}
+
+impl WorkerIdLease for RedisLease {
+    fn worker_id(&self) -> u32 {
+        self.worker_id
+    }
+
+    fn is_valid(&self) -> bool {
+        !self.lost.load(Ordering::SeqCst) && Instant::now() < *self.deadline.lock()
+    }
+}
+
+impl Drop for RedisLease {
+    fn drop(&mut self) {
+        if self.lost.load(Ordering::SeqCst) {
+            return;
+        }
+        let _: redis::RedisResult<i64> = redis::cmd("EVAL")
+            .arg(RELEASE_SCRIPT)
+            .arg(1)
+            .arg(&self.key)
+            .arg(&self.owner)
+            .query(&mut *self.conn.lock());
+    }
+}
+
+/// Background renewal of a `RedisLease`, stopped when dropped
+#[derive(Debug)]
+pub struct RedisLeaseHeartbeat {
+    stop: Option<Sender<()>>,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl Drop for RedisLeaseHeartbeat {
+    fn drop(&mut self) {
+        drop(self.stop.take());
+        if let Some(handle) = self.handle.take() {
+            let _ = handle.join();
+        }
+    }
+}
@@ -0,0 +1,143 @@
+//! # Worker id source
+//!
+//! Derive the worker id from facts of the deployment, e.g. the ordinal of a StatefulSet pod
+//! or the IPv4 address, without any coordination between the generators.
+
+use std::fs;
+use std::net::{IpAddr, Ipv4Addr, UdpSocket};
+
+use crate::generator::{check_worker_id, check_worker_id_bit_len};
+use crate::{IdGeneratorOptions, OptionError};
+
+/// Files the machine id may be read from, in order
+const MACHINE_ID_PATHS: [&str; 2] = ["/etc/machine-id", "/var/lib/dbus/machine-id"];
+
+/// Deployment facts a worker id can be derived from
+///
+/// Every worker id is validated against `worker_id_bit_len` the same as the options.
+/// Only the sources mapping hosts one to one to worker ids, i.e. `PodOrdinal` and `Env`,
+/// are free of collisions. `Ipv4` and `MachineIdHash` are collision free only if the hosts differ
+/// in the low bits of their addresses, or their hashes happen not to collide.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum WorkerIdSource {
+    /// Ordinal suffix of the hostname, e.g. `3` of `web-3` in a Kubernetes StatefulSet
+    PodOrdinal,
+    /// Low `worker_id_bit_len` bits of the IPv4 address of the interface routing outbound traffic
+    Ipv4,
+    /// Worker id in the env var of the name
+    Env(String),
+    /// Stable hash of the machine id in `/etc/machine-id`, folded into `worker_id_bit_len` bits
+    MachineIdHash,
+}
+
+impl WorkerIdSource {
+    /// Derive the worker id from the source
+    pub fn worker_id(&self, worker_id_bit_len: u8) -> Result<u32, OptionError> {
+        match self {
+            WorkerIdSource::PodOrdinal => ordinal_worker_id(&hostname()?, worker_id_bit_len),
+            WorkerIdSource::Ipv4 => ipv4_worker_id(local_ipv4()?, worker_id_bit_len),
+            WorkerIdSource::Env(name) => {
+                let value = std::env::var(name).map_err(|e| {
+                    OptionError::WorkerIdUnavailable(format!("env var {name}: {e}"))
+                })?;
+                let worker_id = value.trim().parse().map_err(|_| {
+                    OptionError::WorkerIdUnavailable(format!(
+                        "env var {name} is not a worker id: {value:?}"
+                    ))
+                })?;
+                check_worker_id_bit_len(worker_id_bit_len)?;
+                check_worker_id(worker_id, worker_id_bit_len)?;
+                Ok(worker_id)
+            }
+            WorkerIdSource::MachineIdHash => hash_worker_id(&machine_id()?, worker_id_bit_len),
+        }
+    }
+
+    /// Set the worker id derived from the source into the options, along with the bit length
+    pub fn apply(
+        &self,
+        options: IdGeneratorOptions,
+        worker_id_bit_len: u8,
+    ) -> Result<IdGeneratorOptions, OptionError> {
+        let worker_id = self.worker_id(worker_id_bit_len)?;
+        Ok(options
+            .worker_id(worker_id)
+            .worker_id_bit_len(worker_id_bit_len))
+    }
+}
+
+/// Parse the worker id from the ordinal suffix of the hostname, e.g. `3` of `web-3`
+pub fn ordinal_worker_id(hostname: &str, worker_id_bit_len: u8) -> Result<u32, OptionError> {
+    check_worker_id_bit_len(worker_id_bit_len)?;
+    // Only the first label, in case of a fully qualified name
+    let name = hostname.trim().split('.').next().unwrap_or_default();
+    let worker_id = name
+        .rsplit_once('-')
+        .and_then(|(_, ordinal)| ordinal.parse().ok())
+        .ok_or_else(|| {
+            OptionError::WorkerIdUnavailable(format!("no ordinal suffix in hostname {hostname:?}"))
+        })?;
+    check_worker_id(worker_id, worker_id_bit_len)?;
+    Ok(worker_id)
+}
+
+/// Take the low `worker_id_bit_len` bits of the IPv4 address as the worker id
+pub fn ipv4_worker_id(addr: Ipv4Addr, worker_id_bit_len: u8) -> Result<u32, OptionError> {
+    check_worker_id_bit_len(worker_id_bit_len)?;
+    Ok(u32::from(addr) & ((1 << worker_id_bit_len) - 1))
+}
+
+/// Fold the FNV-1a hash of the machine id into `worker_id_bit_len` bits as the worker id
+///
+/// The hash is stable across processes, platforms and versions of this crate.
+pub fn hash_worker_id(machine_id: &str, worker_id_bit_len: u8) -> Result<u32, OptionError> {
+    check_worker_id_bit_len(worker_id_bit_len)?;
+    let hash = machine_id
+        .trim()
+        .bytes()
+        .fold(0xcbf29ce484222325u64, |hash, byte| {
+            (hash ^ byte as u64).wrapping_mul(0x100000001b3)
+        });
+    Ok((hash ^ (hash >> 32)) as u32 & ((1 << worker_id_bit_len) - 1))
+}
+
+fn hostname() -> Result<String, OptionError> {
+    if let Ok(hostname) = std::env::var("HOSTNAME") {
+        return Ok(hostname);
+    }
+    fs::read_to_string("/proc/sys/kernel/hostname")
+        .or_else(|_| fs::read_to_string("/etc/hostname"))
+        .map_err(|e| OptionError::WorkerIdUnavailable(format!("hostname: {e}")))
+}
+
+fn local_ipv4() -> Result<Ipv4Addr, OptionError> {
+    // Connecting a UDP socket only looks up the route, nothing is sent
+    let addr = UdpSocket::bind((Ipv4Addr::UNSPECIFIED, 0))
+        .and_then(|socket| {
+            socket.connect((Ipv4Addr::new(192, 0, 2, 1), 9))?;
+            socket.local_addr()
+        })
+        .map_err(|e| OptionError::WorkerIdUnavailable(format!("IPv4 address: {e}")))?;
+    match addr.ip() {
+        IpAddr::V4(ip) if !ip.is_unspecified() => Ok(ip),
+        ip => Err(OptionError::WorkerIdUnavailable(format!(
+            "no IPv4 address, got {ip}"
+        ))),
+    }
+}
+
+fn machine_id() -> Result<String, OptionError> {
+    read_machine_id(&MACHINE_ID_PATHS)
+}
+
+/// Read the first non-empty machine id of the paths, skipping the missing and empty files
+pub(crate) fn read_machine_id(paths: &[&str]) -> Result<String, OptionError> {
+    paths
+        .iter()
+        .find_map(|path| {
+            fs::read_to_string(path)
+                .ok()
+                .filter(|machine_id| !machine_id.trim().is_empty())
+        })
+        .ok_or_else(|| OptionError::WorkerIdUnavailable("no machine id".to_string()))
+}
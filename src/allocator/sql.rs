@@ -0,0 +1,274 @@
+//! # SQL lease allocator
+//!
+//! Allocate worker ids among a fleet of hosts sharing a database, each leasing a row of a table
+//! until it expires, with a heartbeat keeping the lease and the generator in step.
+//! The statements are run by a `SqlLeaseBackend`, e.g. `SqliteLeaseBackend` (feature `sqlite`).
+
+use std::fmt::Debug;
+use std::sync::atomic::{AtomicBool, AtomicI64, AtomicU32, Ordering};
+use std::sync::mpsc::{self, RecvTimeoutError, Sender};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use parking_lot::Mutex;
+
+use super::{max_worker_id, WorkerIdAllocator, WorkerIdLease};
+use crate::{AllocateError, IdGenerator, IdGeneratorOptions};
+
+/// Statements on the lease rows `(worker_id, owner, expires_at)` of a database, run for `SqlLeaseAllocator`
+///
+/// `expires_at` is in milliseconds since the Unix epoch, and every statement reads the current time
+/// from the clock of the database, so that expiry does not depend on the clocks of the hosts.
+pub trait SqlLeaseBackend: Debug + Send + Sync {
+    /// Lease the lowest worker id up to `max_worker_id` without an unexpired row to the owner for `lease_millis`,
+    /// atomically, returning the worker id and its `expires_at`, `None` if all of them are held
+    fn claim(
+        &self,
+        owner: &str,
+        max_worker_id: u32,
+        lease_millis: i64,
+    ) -> Result<Option<(u32, i64)>, AllocateError>;
+
+    /// Extend the unexpired row `(worker_id, owner, expires_at)` to `lease_millis` from now,
+    /// returning the new `expires_at`, `None` if the row has expired or been taken over
+    fn renew(
+        &self,
+        worker_id: u32,
+        owner: &str,
+        expires_at: i64,
+        lease_millis: i64,
+    ) -> Result<Option<i64>, AllocateError>;
+
+    /// Delete the row `(worker_id, owner, expires_at)` if it has not been taken over
+    fn release(&self, worker_id: u32, owner: &str, expires_at: i64) -> Result<(), AllocateError>;
+}
+
+/// Allocate worker ids by leasing rows of a database table, for a fleet of hosts sharing the database
+///
+/// The rows are read and written by a `SqlLeaseBackend`, so any database with a driver can be used.
+///
+/// Each row `(worker_id, owner, expires_at)` leases a worker id to its owner until `expires_at`
+/// (in milliseconds since the Unix epoch). A worker id is free if it has no row or its lease has expired,
+/// so the worker ids of crashed owners are taken over once their leases expire.
+///
+/// Expiry is only ever compared with the clock of the database, so the clocks of the hosts need not agree.
+/// Each host counts the lease down on its own monotonic clock from when it was requested instead,
+/// and treats it as lost `safety_margin` before it expires, default to a tenth of the lease duration.
+///
+/// Leases must be renewed before expiring, see `SqlLease::renew` and `SqlLease::keep_alive`.
+#[derive(Debug, Clone)]
+pub struct SqlLeaseAllocator {
+    backend: Arc<dyn SqlLeaseBackend>,
+    owner: String,
+    lease_duration: Duration,
+    safety_margin: Duration,
+}
+
+impl SqlLeaseAllocator {
+    /// Create an allocator on a SQLite connection, creating the lease table if not exists
+    #[cfg(feature = "sqlite")]
+    pub fn new<S: Into<String>>(
+        conn: rusqlite::Connection,
+        owner: S,
+        lease_duration: Duration,
+    ) -> Result<Self, AllocateError> {
+        let backend = super::SqliteLeaseBackend::new(conn)?;
+        Ok(SqlLeaseAllocator::with_backend(
+            backend,
+            owner,
+            lease_duration,
+        ))
+    }
+
+    /// Create an allocator running the lease statements on the given backend
+    pub fn with_backend<B: SqlLeaseBackend + 'static, S: Into<String>>(
+        backend: B,
+        owner: S,
+        lease_duration: Duration,
+    ) -> Self {
+        SqlLeaseAllocator {
+            backend: Arc::new(backend),
+            owner: owner.into(),
+            lease_duration,
+            safety_margin: lease_duration / 10,
+        }
+    }
+
+    /// Treat the leases as lost the given margin before they expire, to allow for the latency of the database
+    pub fn with_safety_margin(mut self, safety_margin: Duration) -> Self {
+        self.safety_margin = safety_margin;
+        self
+    }
+
+    pub fn owner(&self) -> &str {
+        &self.owner
+    }
+
+    fn lease_millis(&self) -> i64 {
+        self.lease_duration.as_millis() as i64
+    }
+
+    /// Until when a lease requested at `requested` is valid, by the monotonic clock of this host
+    fn valid_until(&self, requested: Instant) -> Instant {
+        requested + self.lease_duration.saturating_sub(self.safety_margin)
+    }
+}
+
+impl WorkerIdAllocator for SqlLeaseAllocator {
+    type Lease = SqlLease;
+
+    fn allocate(&self, worker_id_bit_len: u8) -> Result<SqlLease, AllocateError> {
+        let max_worker_id = max_worker_id(worker_id_bit_len)?;
+        let requested = Instant::now();
+        let (worker_id, expires_at) = self
+            .backend
+            .claim(&self.owner, max_worker_id, self.lease_millis())?
+            .ok_or(AllocateError::Exhausted(max_worker_id))?;
+        Ok(SqlLease {
+            worker_id,
+            worker_id_bit_len,
+            expires_at: AtomicI64::new(expires_at),
+            valid_until: Mutex::new(self.valid_until(requested)),
+            lost: AtomicBool::new(false),
+            allocator: self.clone(),
+        })
+    }
+}
+
+/// Worker id leased by a row of the lease table, deleted when dropped
+///
+/// The `expires_at` of the row written by this lease identifies it, so the lease is lost
+/// once the row is taken over by another owner, even if it is renewed afterwards.
+#[derive(Debug)]
+pub struct SqlLease {
+    worker_id: u32,
+    worker_id_bit_len: u8,
+    expires_at: AtomicI64,
+    valid_until: Mutex<Instant>,
+    lost: AtomicBool,
+    allocator: SqlLeaseAllocator,
+}
+
+impl SqlLease {
+    /// When the lease expires by the database clock, in milliseconds since the Unix epoch
+    pub fn expires_at(&self) -> i64 {
+        self.expires_at.load(Ordering::SeqCst)
+    }
+
+    /// Extend the lease by the lease duration, `false` if the lease is lost
+    pub fn renew(&self) -> Result<bool, AllocateError> {
+        if self.lost.load(Ordering::SeqCst) {
+            return Ok(false);
+        }
+        let requested = Instant::now();
+        let new_expires_at = self.allocator.backend.renew(
+            self.worker_id,
+            &self.allocator.owner,
+            self.expires_at(),
+            self.allocator.lease_millis(),
+        )?;
+        let new_expires_at = match new_expires_at {
+            Some(new_expires_at) => new_expires_at,
+            None => {
+                self.lost.store(true, Ordering::SeqCst);
+                return Ok(false);
+            }
+        };
+        self.expires_at.store(new_expires_at, Ordering::SeqCst);
+        *self.valid_until.lock() = self.allocator.valid_until(requested);
+        Ok(true)
+    }
+
+    /// Renew the lease in a background thread every `interval`, which should be well below the lease duration.
+    ///
+    /// The lease is set into the generator with `set_lease`, so it stops generating ids as soon as the lease
+    /// is lost or expires. A new worker id is then allocated with the same `worker_id_bit_len`, retried on
+    /// every beat until it succeeds, and the generator resumes once it is set with `set_options` and `set_lease`.
+    pub fn keep_alive(
+        self: &Arc<Self>,
+        generator: IdGenerator,
+        interval: Duration,
+    ) -> SqlLeaseHeartbeat {
+        let worker_id = Arc::new(AtomicU32::new(self.worker_id));
+        let current_worker_id = worker_id.clone();
+        let mut lease = self.clone();
+        generator.set_lease(lease.clone());
+        let (stop, stopped) = mpsc::channel();
+        let handle = thread::spawn(move || {
+            while let Err(RecvTimeoutError::Timeout) = stopped.recv_timeout(interval) {
+                let _ = lease.renew();
+                if lease.is_valid() {
+                    continue;
+                }
+                // The generator keeps the invalid lease, and generates no id, until a new one is applied
+                let new_lease = match lease.allocator.allocate(lease.worker_id_bit_len) {
+                    Ok(new_lease) => Arc::new(new_lease),
+                    Err(_) => continue,
+                };
+                if generator
+                    .set_options(new_lease.apply(IdGeneratorOptions::new()))
+                    .is_ok()
+                {
+                    generator.set_lease(new_lease.clone());
+                    current_worker_id.store(new_lease.worker_id, Ordering::SeqCst);
+                    lease = new_lease;
+                }
+            }
+        });
+        SqlLeaseHeartbeat {
+            worker_id,
+            stop: Some(stop),
+            handle: Some(handle),
+        }
+    }
+}
+
+impl WorkerIdLease for SqlLease {
+    fn worker_id(&self) -> u32 {
+        self.worker_id
+    }
+
+    fn is_valid(&self) -> bool {
+        !self.lost.load(Ordering::SeqCst) && Instant::now() < *self.valid_until.lock()
+    }
+}
+
+impl Drop for SqlLease {
+    fn drop(&mut self) {
+        if self.lost.load(Ordering::SeqCst) {
+            return;
+        }
+        let _ = self.allocator.backend.release(
+            self.worker_id,
+            &self.allocator.owner,
+            self.expires_at(),
+        );
+    }
+}
+
+/// Background renewal of a `SqlLease`, stopped when dropped
+///
+/// The lease is released once the generator it is set into drops it too.
+#[derive(Debug)]
+pub struct SqlLeaseHeartbeat {
+    worker_id: Arc<AtomicU32>,
+    stop: Option<Sender<()>>,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl SqlLeaseHeartbeat {
+    /// The worker id currently held
+    pub fn worker_id(&self) -> u32 {
+        self.worker_id.load(Ordering::SeqCst)
+    }
+}
+
+impl Drop for SqlLeaseHeartbeat {
+    fn drop(&mut self) {
+        drop(self.stop.take());
+        if let Some(handle) = self.handle.take() {
+            let _ = handle.join();
+        }
+    }
+}
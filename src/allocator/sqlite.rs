@@ -0,0 +1,124 @@
+//! # SQLite lease backend
+//!
+//! Lease table statements of `SqlLeaseAllocator` on SQLite, for local use and tests.
+
+use std::collections::HashSet;
+
+use parking_lot::Mutex;
+use rusqlite::{params, Connection, OptionalExtension, TransactionBehavior};
+
+use super::SqlLeaseBackend;
+use crate::AllocateError;
+
+/// Table of the lease rows, created if not exists
+const LEASE_TABLE: &str = "idgenerator_worker_leases";
+
+/// Current time of the database clock in milliseconds since the Unix epoch
+const DB_NOW: &str = "CAST(unixepoch('subsec') * 1000 AS INTEGER)";
+
+fn backend(e: rusqlite::Error) -> AllocateError {
+    AllocateError::Backend(e.to_string())
+}
+
+/// Lease table `idgenerator_worker_leases` in a SQLite database
+#[derive(Debug)]
+pub struct SqliteLeaseBackend {
+    conn: Mutex<Connection>,
+}
+
+impl SqliteLeaseBackend {
+    /// Create a backend on the connection, creating the lease table if not exists
+    pub fn new(conn: Connection) -> Result<Self, AllocateError> {
+        conn.execute(
+            &format!(
+                "CREATE TABLE IF NOT EXISTS {LEASE_TABLE} (
+                    worker_id INTEGER PRIMARY KEY,
+                    owner TEXT NOT NULL,
+                    expires_at INTEGER NOT NULL
+                )"
+            ),
+            [],
+        )
+        .map_err(backend)?;
+        Ok(SqliteLeaseBackend {
+            conn: Mutex::new(conn),
+        })
+    }
+}
+
+impl SqlLeaseBackend for SqliteLeaseBackend {
+    fn claim(
+        &self,
+        owner: &str,
+        max_worker_id: u32,
+        lease_millis: i64,
+    ) -> Result<Option<(u32, i64)>, AllocateError> {
+        let mut conn = self.conn.lock();
+        // Take the write lock up front, so no other allocator can claim the same worker id in between
+        let tx = conn
+            .transaction_with_behavior(TransactionBehavior::Immediate)
+            .map_err(backend)?;
+        let now: i64 = tx
+            .query_row(&format!("SELECT {DB_NOW}"), [], |row| row.get(0))
+            .map_err(backend)?;
+        let held = tx
+            .prepare(&format!(
+                "SELECT worker_id FROM {LEASE_TABLE} WHERE worker_id <= ?1 AND expires_at > ?2"
+            ))
+            .and_then(|mut stmt| {
+                stmt.query_map(params![max_worker_id, now], |row| row.get(0))?
+                    .collect::<Result<HashSet<u32>, _>>()
+            })
+            .map_err(backend)?;
+        let worker_id = match (0..=max_worker_id).find(|worker_id| !held.contains(worker_id)) {
+            Some(worker_id) => worker_id,
+            None => return Ok(None),
+        };
+        let expires_at = now + lease_millis;
+        tx.execute(
+            &format!(
+                "INSERT INTO {LEASE_TABLE} (worker_id, owner, expires_at) VALUES (?1, ?2, ?3)
+                ON CONFLICT (worker_id) DO UPDATE SET owner = excluded.owner, expires_at = excluded.expires_at"
+            ),
+            params![worker_id, owner, expires_at],
+        )
+        .map_err(backend)?;
+        tx.commit().map_err(backend)?;
+        Ok(Some((worker_id, expires_at)))
+    }
+
+    fn renew(
+        &self,
+        worker_id: u32,
+        owner: &str,
+        expires_at: i64,
+        lease_millis: i64,
+    ) -> Result<Option<i64>, AllocateError> {
+        self.conn
+            .lock()
+            .query_row(
+                &format!(
+                    "UPDATE {LEASE_TABLE} SET expires_at = {DB_NOW} + ?1
+                    WHERE worker_id = ?2 AND owner = ?3 AND expires_at = ?4 AND expires_at > {DB_NOW}
+                    RETURNING expires_at"
+                ),
+                params![lease_millis, worker_id, owner, expires_at],
+                |row| row.get(0),
+            )
+            .optional()
+            .map_err(backend)
+    }
+
+    fn release(&self, worker_id: u32, owner: &str, expires_at: i64) -> Result<(), AllocateError> {
+        self.conn
+            .lock()
+            .execute(
+                &format!(
+                    "DELETE FROM {LEASE_TABLE} WHERE worker_id = ?1 AND owner = ?2 AND expires_at = ?3"
+                ),
+                params![worker_id, owner, expires_at],
+            )
+            .map_err(backend)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,167 @@
+//! # Atomic
+//!
+//! Lock-free variant of the unique id generator.
+//!
+//! `AtomicIdGenerator` packs the last time tick and sequence number into a single `AtomicU64`
+//! and generates ids with compare-and-swap, so it can be shared between threads without any lock.
+//!
+//! It trades some of the semantics of `CoreIdGenerator` for it:
+//!
+//! - Drift is bounded by `top_over_cost_count` ticks ahead of the clock in total, rather than per over-cost term.
+//!   Once that far ahead, it waits for the clock to catch up.
+//! - There is no turn back handling: when the clock turns back, ids keep being generated after the last tick
+//!   as if drifting, and the reserved turn back sequence numbers are never used.
+
+use std::hint::spin_loop;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::thread::sleep;
+
+use crate::generator::InstanceOptions;
+use crate::{
+    Clock, GenerateError, IdGeneratorOptions, IdWidth, OptionError, StartupStrategy, SystemClock,
+    TimeUnit,
+};
+
+/// Bit length of the sequence number inside the packed state, large enough for any valid `seq_bit_len`
+const STATE_SEQ_BIT_LEN: u8 = 22;
+const STATE_SEQ_MASK: u64 = (1 << STATE_SEQ_BIT_LEN) - 1;
+
+/// Lock-free unique id generator.
+///
+/// Generates ids with the same layout as `CoreIdGenerator`, but with the drift and turn back described in the module.
+#[derive(Debug)]
+pub struct AtomicIdGenerator {
+    options: InstanceOptions,
+    clock: Box<dyn Clock>,
+    timestamp_shift: u8,
+    max_time_tick: i64,
+    seq_shift: u8,
+    node_part: i64,
+    /// Last time tick in the high bits and last sequence number in the low `STATE_SEQ_BIT_LEN` bits
+    state: AtomicU64,
+}
+
+impl AtomicIdGenerator {
+    /// Create a generator with the given options
+    ///
+    /// Options not set will be given the default value.
+    pub fn new(options: IdGeneratorOptions) -> Result<Self, OptionError> {
+        AtomicIdGenerator::with_clock(options, SystemClock)
+    }
+
+    /// Create a generator with the given options, reading the current time from the given clock
+    pub fn with_clock<C: Clock + 'static>(
+        options: IdGeneratorOptions,
+        clock: C,
+    ) -> Result<Self, OptionError> {
+        let mut options: InstanceOptions = options.into();
+        options.check(clock.now_millis())?;
+        if options.id_width == IdWidth::Bits128 {
+            return Err(OptionError::InvalidIdWidth(
+                "should have at most 64 bits for AtomicIdGenerator".to_string(),
+            ));
+        }
+        // Nothing has been generated by a new generator, so only the fixed sleep applies
+        let mut state = 0;
+        if options.startup_strategy == StartupStrategy::Sleep {
+            if options.method == 1 {
+                sleep(std::time::Duration::from_millis(500));
+            } else {
+                // Classic snowflake never borrows future ticks, so starting as if the sequence of the current tick
+                // was exhausted skips the ids generated before
+                let current_time_tick =
+                    (clock.now_millis() - options.base_time).div_euclid(options.time_unit.millis());
+                state =
+                    ((current_time_tick as u64) << STATE_SEQ_BIT_LEN) | options.max_seq_num as u64;
+            }
+        }
+        let shifts = options.shifts();
+        Ok(AtomicIdGenerator {
+            timestamp_shift: shifts.timestamp_shift,
+            max_time_tick: shifts.max_time_tick,
+            seq_shift: shifts.seq_shift,
+            node_part: shifts.node_part,
+            options,
+            clock: Box::new(clock),
+            state: AtomicU64::new(state),
+        })
+    }
+
+    pub fn get_options(&self) -> IdGeneratorOptions {
+        self.options.clone().into()
+    }
+
+    /// Get a unique id
+    pub fn next_id(&self) -> i64 {
+        self.generate(i64::MAX).unwrap_or_else(|| unreachable!())
+    }
+
+    /// Get a unique id, or an error if the time tick overflows
+    ///
+    /// Nothing is consumed when it fails.
+    pub fn try_next_id(&self) -> Result<i64, GenerateError> {
+        self.generate(self.max_time_tick)
+            .ok_or(GenerateError::TimeTickOverflow(self.max_time_tick))
+    }
+
+    /// Generate an id, `None` without changing the state if its time tick would exceed `max_time_tick`
+    fn generate(&self, max_time_tick: i64) -> Option<i64> {
+        let mut state = self.state.load(Ordering::Acquire);
+        loop {
+            let (time_tick, seq_number) = match self.next_state(state) {
+                Some(next) => next,
+                None => {
+                    // Spin for the next millisecond, but sleep for the coarser time units
+                    match self.options.time_unit {
+                        TimeUnit::Millisecond => spin_loop(),
+                        _ => sleep(std::time::Duration::from_millis(1)),
+                    }
+                    state = self.state.load(Ordering::Acquire);
+                    continue;
+                }
+            };
+            if time_tick > max_time_tick {
+                return None;
+            }
+            let new_state = ((time_tick as u64) << STATE_SEQ_BIT_LEN) | seq_number as u64;
+            match self.state.compare_exchange_weak(
+                state,
+                new_state,
+                Ordering::AcqRel,
+                Ordering::Acquire,
+            ) {
+                Ok(_) => {
+                    let id = (time_tick << self.timestamp_shift)
+                        + self.node_part
+                        + ((seq_number as i64) << self.seq_shift);
+                    return Some(id);
+                }
+                Err(actual) => state = actual,
+            }
+        }
+    }
+
+    /// Compute the time tick and sequence number following the given state,
+    /// `None` if it has to wait for the time to move forward
+    fn next_state(&self, state: u64) -> Option<(i64, u32)> {
+        let last_time_tick = (state >> STATE_SEQ_BIT_LEN) as i64;
+        let seq_number = (state & STATE_SEQ_MASK) as u32;
+        let current_time_tick = (self.clock.now_millis() - self.options.base_time)
+            .div_euclid(self.options.time_unit.millis());
+
+        if current_time_tick > last_time_tick {
+            Some((current_time_tick, self.options.min_seq_num))
+        } else if self.options.method == 2 && current_time_tick < last_time_tick {
+            // Classic snowflake never generates ids ahead of the current time
+            None
+        } else if seq_number < self.options.max_seq_num {
+            Some((last_time_tick, seq_number + 1))
+        } else if self.options.method == 1
+            && last_time_tick + 1 - current_time_tick <= self.options.top_over_cost_count as i64
+        {
+            Some((last_time_tick + 1, self.options.min_seq_num))
+        } else {
+            None
+        }
+    }
+}
@@ -0,0 +1,57 @@
+//! # Block
+//!
+//! A block of unique ids reserved from a generator in advance.
+//!
+//! Ids in the block are already marked as consumed in the generator,
+//! so they can be cached and handed out locally without touching the generator again.
+
+use std::collections::VecDeque;
+use std::ops::Range;
+
+/// A block of reserved unique ids, consumed as an iterator
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct IdBlock {
+    ranges: VecDeque<Range<i64>>,
+    len: usize,
+}
+
+impl IdBlock {
+    /// Append a range of consecutive ids to the block
+    pub(crate) fn push(&mut self, range: Range<i64>) {
+        self.len += (range.end - range.start) as usize;
+        match self.ranges.back_mut() {
+            Some(last) if last.end == range.start => last.end = range.end,
+            _ => self.ranges.push_back(range),
+        }
+    }
+
+    /// Number of ids left in the block
+    pub fn remaining(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+}
+
+impl Iterator for IdBlock {
+    type Item = i64;
+
+    fn next(&mut self) -> Option<i64> {
+        let range = self.ranges.front_mut()?;
+        let id = range.start;
+        range.start += 1;
+        if range.start == range.end {
+            self.ranges.pop_front();
+        }
+        self.len -= 1;
+        Some(id)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.len, Some(self.len))
+    }
+}
+
+impl ExactSizeIterator for IdBlock {}
@@ -0,0 +1,152 @@
+//! # Checksum
+//!
+//! Check characters appended to the encoded ids, so ids typed by hand with a typo are rejected
+//! instead of looking up another valid id.
+//!
+//! - `Checksum::Damm`: for `Encoding::Decimal`, detects all single-digit errors and adjacent transpositions.
+//! - `Checksum::LuhnModN`: for any encoding, detects all single-digit errors and most adjacent transpositions,
+//!   all but the one of the first and the last digits of the alphabet, e.g. `09` and `90` in decimal.
+//! - `Checksum::Mod37`: Crockford's mod 37 for `Encoding::Crockford32`, detects all single-character errors
+//!   and adjacent transpositions, with the extra check symbols `*~$=U`.
+
+use crate::encoding::canonical_crockford32;
+use crate::{ChecksumError, Encoding, EncodingError};
+
+/// Weakly totally anti-symmetric quasigroup of order 10 with a zero diagonal, from Damm's thesis
+const DAMM_TABLE: [[u8; 10]; 10] = [
+    [0, 3, 1, 7, 5, 9, 8, 6, 4, 2],
+    [7, 0, 9, 2, 1, 5, 4, 8, 6, 3],
+    [4, 2, 0, 6, 8, 7, 1, 3, 5, 9],
+    [1, 7, 5, 0, 9, 8, 3, 4, 2, 6],
+    [6, 1, 2, 3, 0, 4, 5, 9, 7, 8],
+    [3, 6, 7, 4, 2, 0, 9, 5, 8, 1],
+    [5, 8, 6, 9, 7, 2, 0, 1, 3, 4],
+    [8, 9, 4, 5, 3, 6, 2, 0, 1, 7],
+    [9, 4, 3, 8, 6, 1, 7, 2, 0, 5],
+    [2, 5, 8, 1, 4, 3, 6, 7, 9, 0],
+];
+
+/// Crockford base32 alphabet followed by the check symbols for 32 to 36
+const MOD37_ALPHABET: &[u8] = b"0123456789ABCDEFGHJKMNPQRSTVWXYZ*~$=U";
+
+/// Check character algorithm
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Checksum {
+    /// Damm algorithm, only for `Encoding::Decimal`
+    Damm,
+    /// Luhn mod N algorithm, with N the radix of the encoding
+    LuhnModN,
+    /// Crockford's mod 37 check symbol, only for `Encoding::Crockford32`
+    Mod37,
+}
+
+impl Checksum {
+    /// Whether the checksum can be used with the encoding
+    pub fn supports(self, encoding: Encoding) -> bool {
+        match self {
+            Checksum::Damm => encoding == Encoding::Decimal,
+            Checksum::LuhnModN => true,
+            Checksum::Mod37 => encoding == Encoding::Crockford32,
+        }
+    }
+
+    /// Check character of the encoded id
+    pub fn check_char(self, encoding: Encoding, encoded: &str) -> Result<char, ChecksumError> {
+        let check = self.check_value(encoding, encoded)?;
+        Ok(self.alphabet(encoding)[check as usize] as char)
+    }
+
+    fn check_value(self, encoding: Encoding, encoded: &str) -> Result<u64, ChecksumError> {
+        if !self.supports(encoding) {
+            return Err(ChecksumError::Unsupported(self, encoding));
+        }
+        let digits = digits(encoding, encoded)?;
+        let check = match self {
+            Checksum::Damm => digits.iter().fold(0, |interim, &digit| {
+                DAMM_TABLE[interim as usize][digit as usize] as u64
+            }),
+            Checksum::LuhnModN => luhn_mod_n(&digits, encoding.radix()),
+            Checksum::Mod37 => digits
+                .iter()
+                .fold(0, |rest, &digit| (rest * 32 + digit) % 37),
+        };
+        Ok(check)
+    }
+
+    /// Append the check character to the encoded id, e.g. from `next_id_string`
+    pub fn append(self, encoding: Encoding, encoded: &str) -> Result<String, ChecksumError> {
+        let check = self.check_char(encoding, encoded)?;
+        let mut checked = encoded.to_string();
+        checked.push(check);
+        Ok(checked)
+    }
+
+    /// Verify the check character at the end of the string and decode the id before it
+    ///
+    /// The check character is read as the digits of the encoding, e.g. case-insensitively for Crockford base32.
+    pub fn validate(self, encoding: Encoding, checked: &str) -> Result<u64, ChecksumError> {
+        let mut chars = checked.chars();
+        let found = chars.next_back().ok_or(EncodingError::Empty)?;
+        let encoded = chars.as_str();
+        let expected = self.check_value(encoding, encoded)?;
+        let found_value = match self {
+            Checksum::Mod37 => {
+                let c = canonical_crockford32(found);
+                MOD37_ALPHABET
+                    .iter()
+                    .position(|&check| check as char == c)
+                    .map(|check| check as u64)
+            }
+            _ => encoding.digit(found),
+        }
+        .ok_or(ChecksumError::InvalidCheckChar(found))?;
+        if found_value != expected {
+            return Err(ChecksumError::Mismatch(
+                self.alphabet(encoding)[expected as usize] as char,
+                found,
+            ));
+        }
+        Ok(encoding.decode(encoded)?)
+    }
+
+    /// Characters of the check values
+    fn alphabet(self, encoding: Encoding) -> &'static [u8] {
+        match self {
+            Checksum::Mod37 => MOD37_ALPHABET,
+            _ => encoding.alphabet(),
+        }
+    }
+}
+
+/// Values of the digits, hyphens skipped for Crockford base32 as `Encoding::decode`
+fn digits(encoding: Encoding, encoded: &str) -> Result<Vec<u64>, EncodingError> {
+    let mut digits = Vec::with_capacity(encoded.len());
+    for (index, c) in encoded.char_indices() {
+        if encoding == Encoding::Crockford32 && c == '-' {
+            continue;
+        }
+        digits.push(
+            encoding
+                .digit(c)
+                .ok_or(EncodingError::InvalidChar(c, index))?,
+        );
+    }
+    if digits.is_empty() {
+        return Err(EncodingError::Empty);
+    }
+    Ok(digits)
+}
+
+/// Check value of Luhn mod N, doubling every other digit from the rightmost one
+fn luhn_mod_n(digits: &[u64], radix: u64) -> u64 {
+    let sum: u64 = digits
+        .iter()
+        .rev()
+        .enumerate()
+        .map(|(i, &digit)| {
+            let addend = if i % 2 == 0 { digit * 2 } else { digit };
+            addend / radix + addend % radix
+        })
+        .sum();
+    (radix - sum % radix) % radix
+}
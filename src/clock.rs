@@ -0,0 +1,94 @@
+//! # Clock
+//!
+//! Clock sources the snowflake worker reads the current time from:
+//!
+//! - `SystemClock`: the system wall clock, used by default.
+//! - `MonotonicClock`: the wall clock sampled once, then advanced with a monotonic `Instant`, so it is not affected by NTP step adjustments.
+//! - `ManualClock`: a clock only moving when told to, for tests.
+
+use std::fmt::Debug;
+use std::sync::atomic::{AtomicI64, Ordering};
+use std::sync::Arc;
+use std::time::Instant;
+
+use chrono::Utc;
+
+/// A source of the current time for the generator
+pub trait Clock: Debug + Send + Sync {
+    /// Current UTC time, in milliseconds since the Unix epoch
+    fn now_millis(&self) -> i64;
+}
+
+/// The system wall clock
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now_millis(&self) -> i64 {
+        Utc::now().timestamp_millis()
+    }
+}
+
+/// Wall clock sampled once at creation, then advanced with a monotonic `Instant`.
+///
+/// The time it reports never goes backwards, but may slowly deviate from the system wall clock.
+#[derive(Debug, Clone, Copy)]
+pub struct MonotonicClock {
+    anchor_millis: i64,
+    anchor: Instant,
+}
+
+impl MonotonicClock {
+    pub fn new() -> Self {
+        MonotonicClock {
+            anchor_millis: Utc::now().timestamp_millis(),
+            anchor: Instant::now(),
+        }
+    }
+}
+
+impl Default for MonotonicClock {
+    fn default() -> Self {
+        MonotonicClock::new()
+    }
+}
+
+impl Clock for MonotonicClock {
+    fn now_millis(&self) -> i64 {
+        self.anchor_millis + self.anchor.elapsed().as_millis() as i64
+    }
+}
+
+/// A clock which only moves when set or advanced manually.
+///
+/// Clones share the same time, so a test can keep a clone to drive the generator's clock.
+/// Note that the generator may wait for the time to move forward (e.g. when the sequence is exhausted),
+/// which will never end unless the clock is advanced from another thread.
+#[derive(Debug, Clone, Default)]
+pub struct ManualClock {
+    millis: Arc<AtomicI64>,
+}
+
+impl ManualClock {
+    pub fn new(millis: i64) -> Self {
+        ManualClock {
+            millis: Arc::new(AtomicI64::new(millis)),
+        }
+    }
+
+    /// Set the current time, in milliseconds since the Unix epoch
+    pub fn set(&self, millis: i64) {
+        self.millis.store(millis, Ordering::SeqCst);
+    }
+
+    /// Move the current time forward (or backward if negative) by the given milliseconds
+    pub fn advance(&self, millis: i64) {
+        self.millis.fetch_add(millis, Ordering::SeqCst);
+    }
+}
+
+impl Clock for ManualClock {
+    fn now_millis(&self) -> i64 {
+        self.millis.load(Ordering::SeqCst)
+    }
+}
@@ -0,0 +1,110 @@
+//! # Decoder
+//!
+//! Split a generated id back into the parts it was composed of.
+//!
+//! The id layout produced by the snowflake worker is `time_tick | datacenter_id | worker_id | sequence`,
+//! where `sequence` occupies the lowest `seq_bit_len` bits, `worker_id` the next `worker_id_bit_len` bits
+//! and `datacenter_id` the next `datacenter_id_bit_len` bits (none by default), unless a custom `IdLayout` is given.
+//! 128-bit ids of `IdWidth::Bits128` are decoded with `decode_u128`.
+
+use chrono::{DateTime, TimeZone, Utc};
+
+use crate::generator::{WIDE_NODE_BIT_LEN, WIDE_TIMESTAMP_BIT_LEN};
+use crate::{IdGeneratorOptions, IdLayout, IdWidth, LayoutField, TimeUnit};
+
+/// Parts of a decoded id
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DecodedId {
+    /// Time units elapsed since `base_time` when the id was generated
+    pub time_tick: i64,
+    /// Absolute UTC timestamp in milliseconds, i.e. `base_time + time_tick * time_unit`
+    pub timestamp: i64,
+    /// Datacenter id of the generator which generated the id, 0 if the layout has no datacenter field
+    pub datacenter_id: u32,
+    /// Worker id of the generator which generated the id
+    pub worker_id: u32,
+    /// Sequence number of the id
+    pub seq: u32,
+    /// Names and values of the custom fields of the layout, if any
+    pub custom: Vec<(String, u64)>,
+    /// Whether the sequence falls in the reserved slots for time turned back, i.e. \[1, 4\]
+    pub is_turn_back: bool,
+    /// Whether the sequence falls in the reserved slot for manually value, i.e. 0
+    pub is_manual: bool,
+}
+
+impl DecodedId {
+    /// The absolute UTC timestamp as a `DateTime`, `None` if it is out of range
+    pub fn datetime(&self) -> Option<DateTime<Utc>> {
+        Utc.timestamp_millis_opt(self.timestamp).single()
+    }
+}
+
+/// Decode an id with the options of the generator which generated it.
+///
+/// Options not set will be given the default value, the same as `CoreIdGenerator::init`.
+/// With `IdWidth::Bits128` the id is widened the same as `next_u128` widens the 64-bit ids, and decoded with `decode_u128`.
+pub fn decode(id: i64, options: &IdGeneratorOptions) -> DecodedId {
+    if options.id_width == Some(IdWidth::Bits128) {
+        return decode_u128(id as u64 as u128, options);
+    }
+    let layout = match &options.layout {
+        Some(layout) => layout.clone(),
+        None => IdLayout::standard(
+            options.id_width.unwrap_or(IdWidth::Bits63).bit_len(),
+            options.datacenter_id_bit_len.unwrap_or(0),
+            options.worker_id_bit_len.unwrap_or(8),
+            options.seq_bit_len.unwrap_or(8),
+        ),
+    };
+    let field = |matches: fn(&LayoutField) -> bool| {
+        let (shift, bit_len) = layout.position(matches);
+        // The time tick may take the sign bit with `IdWidth::Bits64`
+        ((id as u64 >> shift) & ((1 << bit_len) - 1)) as i64
+    };
+
+    let time_tick = field(|f| matches!(f, LayoutField::Timestamp(_)));
+    let datacenter_id = field(|f| matches!(f, LayoutField::Datacenter(_))) as u32;
+    let worker_id = field(|f| matches!(f, LayoutField::Worker(_))) as u32;
+    let seq = field(|f| matches!(f, LayoutField::Sequence(_))) as u32;
+    DecodedId {
+        time_tick,
+        timestamp: timestamp(time_tick, options),
+        datacenter_id,
+        worker_id,
+        seq,
+        custom: layout.custom_values(id),
+        is_turn_back: (1..=4).contains(&seq),
+        is_manual: seq == 0,
+    }
+}
+
+/// Decode a 128-bit id of `IdWidth::Bits128` with the options of the generator which generated it.
+///
+/// The random bits are dropped. Options not set will be given the default value, the same as `CoreIdGenerator::init`.
+pub fn decode_u128(id: u128, options: &IdGeneratorOptions) -> DecodedId {
+    let worker_id_bit_len = options.worker_id_bit_len.unwrap_or(8);
+    let seq_bit_len = options.seq_bit_len.unwrap_or(8);
+    let time_tick = (id >> (128 - WIDE_TIMESTAMP_BIT_LEN)) as i64;
+    let node = (id >> 64) as u32 & ((1 << WIDE_NODE_BIT_LEN) - 1);
+    let seq = ((id as u64) >> (64 - seq_bit_len)) as u32;
+    DecodedId {
+        time_tick,
+        timestamp: timestamp(time_tick, options),
+        datacenter_id: node >> worker_id_bit_len,
+        worker_id: node & ((1 << worker_id_bit_len) - 1),
+        seq,
+        custom: vec![],
+        is_turn_back: (1..=4).contains(&seq),
+        is_manual: seq == 0,
+    }
+}
+
+/// Absolute UTC timestamp in milliseconds of the time tick
+fn timestamp(time_tick: i64, options: &IdGeneratorOptions) -> i64 {
+    let base_time = match options.base_time {
+        Some(base_time) if base_time != 0 => base_time,
+        _ => 1582136402000,
+    };
+    base_time + time_tick * options.time_unit.map_or(1, TimeUnit::millis)
+}
@@ -0,0 +1,145 @@
+//! # Encoding
+//!
+//! Compact string encodings of the ids, for URLs and user-facing references instead of 19-digit decimals.
+//!
+//! - `Encoding::Base62`: `0-9A-Za-z`, the shortest one, but case-sensitive.
+//! - `Encoding::Crockford32`: Crockford base32 `0-9A-HJKMNP-TV-Z`, case-insensitive and free of the ambiguous characters.
+//! - `Encoding::Base36`: `0-9a-z`, case-insensitive.
+//! - `Encoding::Decimal`: `0-9`, the longest one, for ids read out or typed on a keypad.
+//!
+//! The alphabets are in ASCII order, so the fixed-width encoding of `encode_fixed`, padded with leading zeros,
+//! sorts lexicographically in the same order as the numbers, i.e. the order of `next_id`.
+//! Ids are encoded as `u64`, so negative ids of `IdWidth::Bits64` sort after the positive ones the same as `next_u64`.
+
+use crate::EncodingError;
+
+const BASE62_ALPHABET: &[u8] = b"0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz";
+const CROCKFORD32_ALPHABET: &[u8] = b"0123456789ABCDEFGHJKMNPQRSTVWXYZ";
+const BASE36_ALPHABET: &[u8] = b"0123456789abcdefghijklmnopqrstuvwxyz";
+const DECIMAL_ALPHABET: &[u8] = b"0123456789";
+
+/// String encoding of the ids
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Encoding {
+    /// `0-9A-Za-z`, 11 characters in fixed width
+    Base62,
+    /// Crockford base32 `0-9A-HJKMNP-TV-Z`, 13 characters in fixed width
+    ///
+    /// Decoding is case-insensitive, reads `I` and `L` as `1` and `O` as `0`, and ignores hyphens.
+    Crockford32,
+    /// `0-9a-z`, 13 characters in fixed width, decoded case-insensitively
+    Base36,
+    /// `0-9`, 20 characters in fixed width
+    Decimal,
+}
+
+impl Encoding {
+    /// Characters of the digits in ascending order
+    pub fn alphabet(self) -> &'static [u8] {
+        match self {
+            Encoding::Base62 => BASE62_ALPHABET,
+            Encoding::Crockford32 => CROCKFORD32_ALPHABET,
+            Encoding::Base36 => BASE36_ALPHABET,
+            Encoding::Decimal => DECIMAL_ALPHABET,
+        }
+    }
+
+    pub fn radix(self) -> u64 {
+        self.alphabet().len() as u64
+    }
+
+    /// Number of characters of the fixed-width encoding, enough for any `u64`
+    pub fn fixed_width(self) -> usize {
+        match self {
+            Encoding::Base62 => 11,
+            Encoding::Crockford32 | Encoding::Base36 => 13,
+            Encoding::Decimal => 20,
+        }
+    }
+
+    /// Encode the id in the fewest characters
+    pub fn encode(self, id: u64) -> String {
+        let alphabet = self.alphabet();
+        let radix = self.radix();
+        let mut id = id;
+        let mut digits = Vec::with_capacity(self.fixed_width());
+        loop {
+            digits.push(alphabet[(id % radix) as usize]);
+            id /= radix;
+            if id == 0 {
+                break;
+            }
+        }
+        digits.reverse();
+        String::from_utf8(digits).unwrap()
+    }
+
+    /// Encode the id padded with leading zeros to `fixed_width`, so the strings sort in the order of the ids
+    pub fn encode_fixed(self, id: u64) -> String {
+        // All the alphabets start with `0`
+        format!("{:0>width$}", self.encode(id), width = self.fixed_width())
+    }
+
+    /// Decode an id encoded by `encode` or `encode_fixed`
+    pub fn decode(self, encoded: &str) -> Result<u64, EncodingError> {
+        let radix = self.radix();
+        let mut id: u64 = 0;
+        let mut digit_count = 0;
+        for (index, c) in encoded.char_indices() {
+            if self == Encoding::Crockford32 && c == '-' {
+                continue;
+            }
+            let digit = self.digit(c).ok_or(EncodingError::InvalidChar(c, index))?;
+            id = id
+                .checked_mul(radix)
+                .and_then(|id| id.checked_add(digit))
+                .ok_or(EncodingError::Overflow)?;
+            digit_count += 1;
+        }
+        if digit_count == 0 {
+            return Err(EncodingError::Empty);
+        }
+        Ok(id)
+    }
+
+    /// Value of the character as a digit, `None` if it is not in the alphabet
+    pub(crate) fn digit(self, c: char) -> Option<u64> {
+        let digit = match (self, c) {
+            (Encoding::Base62, '0'..='9') => c as u8 - b'0',
+            (Encoding::Base62, 'A'..='Z') => c as u8 - b'A' + 10,
+            (Encoding::Base62, 'a'..='z') => c as u8 - b'a' + 36,
+            (Encoding::Base36, _) => return c.to_digit(36).map(u64::from),
+            (Encoding::Decimal, _) => return c.to_digit(10).map(u64::from),
+            (Encoding::Crockford32, _) => {
+                let c = canonical_crockford32(c);
+                return CROCKFORD32_ALPHABET
+                    .iter()
+                    .position(|&digit| digit as char == c)
+                    .map(|digit| digit as u64);
+            }
+            _ => return None,
+        };
+        Some(digit as u64)
+    }
+}
+
+/// Normalize a Crockford base32 string to its canonical form
+///
+/// Letters are uppercased, `I` and `L` become `1`, `O` becomes `0` and hyphens are removed.
+/// Other characters are kept as is, to be rejected by `Encoding::decode`.
+pub fn normalize_crockford32(encoded: &str) -> String {
+    encoded
+        .chars()
+        .filter(|&c| c != '-')
+        .map(canonical_crockford32)
+        .collect()
+}
+
+/// The character a Crockford base32 character stands for, e.g. `1` for `l`
+pub(crate) fn canonical_crockford32(c: char) -> char {
+    match c.to_ascii_uppercase() {
+        'I' | 'L' => '1',
+        'O' => '0',
+        c => c,
+    }
+}
@@ -1,5 +1,7 @@
 use thiserror::Error;
 
+use crate::{Checksum, Encoding};
+
 #[derive(Debug, Clone, Error, PartialEq)]
 pub enum OptionError {
     #[error("Invalid method.")]
@@ -8,6 +10,8 @@ pub enum OptionError {
     InvalidBaseTime,
     #[error("Invalid worker id: {0}.")]
     InvalidWorkerId(String),
+    #[error("Invalid datacenter id: {0}.")]
+    InvalidDatacenterId(String),
     #[error("Invalid worker id bit length: {0}.")]
     InvalidWorkerIdBitLen(String),
     #[error("Invalid sequence bit length: {0}.")]
@@ -24,4 +28,108 @@ pub enum OptionError {
     InvalidVecLen(u32),
     #[error("Invalid index: {0}.")]
     IndexOutOfRange(usize),
+    #[error("Insufficient horizon: {0}.")]
+    InsufficientHorizon(String),
+    #[error("Key not found: {0}.")]
+    KeyNotFound(String),
+    #[error("Duplicate key: {0}.")]
+    DuplicateKey(String),
+    #[error("Tick store error: {0}.")]
+    TickStore(String),
+    #[error("Clock is behind the time persisted in the tick store by {0} milliseconds.")]
+    ClockBehindTickStore(i64),
+    #[error("Invalid layout: {0}.")]
+    InvalidLayout(String),
+    #[error("Worker id unavailable: {0}.")]
+    WorkerIdUnavailable(String),
+    #[error("Invalid id width: {0}.")]
+    InvalidIdWidth(String),
+}
+
+#[derive(Debug, Clone, Error, PartialEq)]
+pub enum GenerateError {
+    #[error("Time tick overflow: should not exceed {0}.")]
+    TimeTickOverflow(i64),
+    #[error("Clock moved backwards by {0} milliseconds, more than the reserved turn back sequence can absorb.")]
+    ClockMovedBackwards(i64),
+    #[error("Instance not initialized.")]
+    Uninitialized,
+    #[error("Invalid index: {0}.")]
+    IndexOutOfRange(usize),
+    #[error("Key not found: {0}.")]
+    KeyNotFound(String),
+    #[error("Tick store error: {0}.")]
+    TickStore(String),
+    #[error("Lease of worker id {0} is lost.")]
+    LeaseLost(u32),
+    #[error("128-bit ids do not fit in 64 bits, use next_u128.")]
+    IdTooWide,
+}
+
+#[derive(Debug, Clone, Error, PartialEq)]
+pub enum EncodingError {
+    #[error("Empty string.")]
+    Empty,
+    #[error("Invalid character {0:?} at {1}.")]
+    InvalidChar(char, usize),
+    #[error("Overflow: should not exceed 64 bits.")]
+    Overflow,
+}
+
+#[derive(Debug, Clone, Error, PartialEq)]
+pub enum ObfuscationError {
+    #[error("Invalid key version: {0}, should be at most 61.")]
+    InvalidVersion(u8),
+    #[error("Duplicate key version: {0}.")]
+    DuplicateVersion(u8),
+    #[error("Unknown key version: {0}.")]
+    UnknownVersion(u8),
+    #[error("Negative id: {0}.")]
+    NegativeId(i64),
+    #[error("Invalid obfuscated id: {0}.")]
+    InvalidId(String),
+    #[error("Encoding error: {0}")]
+    Encoding(#[from] EncodingError),
+}
+
+#[derive(Debug, Clone, Error, PartialEq)]
+pub enum SqidsError {
+    #[error("Invalid alphabet: {0}.")]
+    InvalidAlphabet(String),
+    #[error("Negative id: {0}.")]
+    NegativeId(i64),
+    #[error("Reached max attempts to avoid the blocklist.")]
+    MaxAttempts,
+}
+
+#[derive(Debug, Clone, Error, PartialEq)]
+pub enum ParseIdError {
+    #[error("Wrong prefix: should be {0}, but {1}.")]
+    WrongPrefix(String, String),
+    #[error("Invalid typed id: {0}.")]
+    Invalid(String),
+    #[error("Encoding error: {0}")]
+    Encoding(#[from] EncodingError),
+}
+
+#[derive(Debug, Clone, Error, PartialEq)]
+pub enum ChecksumError {
+    #[error("{0:?} checksum does not support {1:?} encoding.")]
+    Unsupported(Checksum, Encoding),
+    #[error("Invalid check character {0:?}.")]
+    InvalidCheckChar(char),
+    #[error("Checksum mismatch: should be {0:?}, but {1:?}.")]
+    Mismatch(char, char),
+    #[error("Encoding error: {0}")]
+    Encoding(#[from] EncodingError),
+}
+
+#[derive(Debug, Clone, Error, PartialEq)]
+pub enum AllocateError {
+    #[error("Invalid options: {0}")]
+    Option(#[from] OptionError),
+    #[error("No free worker id in [0, {0}].")]
+    Exhausted(u32),
+    #[error("Allocator backend error: {0}.")]
+    Backend(String),
 }
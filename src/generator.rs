@@ -4,11 +4,21 @@
 //!
 //! Contains the implementation of the snowflake algorithm and the wrapper as `CoreIdGenerator`
 
-use crate::IdGeneratorOptions;
-use chrono::Utc;
+use crate::{
+    decoder, Clock, DecodedId, Encoding, Horizon, IdBlock, IdGeneratorOptions, IdLayout, IdWidth,
+    LayoutField, StartupStrategy, SystemClock, TickStore, TimeUnit, WorkerIdLease,
+};
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+use std::ops::Range;
+use std::sync::Arc;
 use std::thread::sleep;
+use std::time::{Duration, Instant};
 
-use super::error::OptionError;
+use super::error::{GenerateError, OptionError};
+
+/// How long the infallible ways of generating ids wait before retrying when they cannot generate one
+const BLOCKED_RETRY_INTERVAL: Duration = Duration::from_millis(10);
 
 /// Wrapper of the snowflake algorithm worker.
 /// Provide public interfaces to initialize a generator, set options, get options and get a unique id.
@@ -18,11 +28,66 @@ pub struct CoreIdGenerator {
 }
 
 impl CoreIdGenerator {
+    /// Create a generator reading the current time from the given clock
+    pub fn with_clock<C: Clock + 'static>(clock: C) -> Self {
+        let mut generator = CoreIdGenerator::default();
+        generator.set_clock(clock);
+        generator
+    }
+
+    /// Replace the clock the generator reads the current time from
+    pub fn set_clock<C: Clock + 'static>(&mut self, clock: C) {
+        self.worker.clock = Box::new(clock);
+    }
+
+    /// Persist the time ids are generated up to in the store, consulted when initializing the generator
+    ///
+    /// A high-water mark `checkpoint_interval` ahead of the time ids are generated at is saved,
+    /// and saved again only when reached. Set it before calling `init`.
+    ///
+    /// No id is generated past the persisted time: while saving fails, `try_next_id` returns
+    /// `GenerateError::TickStore` and the other ways of generating ids retry until it succeeds.
+    pub fn set_tick_store<S: TickStore + 'static>(
+        &mut self,
+        store: S,
+        checkpoint_interval: Duration,
+    ) {
+        self.worker.tick_store = Some(Box::new(store));
+        self.worker.checkpoint_interval = checkpoint_interval.as_millis() as i64;
+        self.worker.checkpoint_timestamp = i64::MIN;
+    }
+
+    /// Only generate ids while the lease of the worker id is valid
+    ///
+    /// Once the lease is lost, `try_next_id` returns `GenerateError::LeaseLost` and the other ways of
+    /// generating ids panic, or wait for a new lease with `IdGenerator`, so no id is generated with a worker id
+    /// which may be claimed by another generator.
+    /// The lease is shared, e.g. to be renewed in the background, and released when the last clone is dropped.
+    pub fn set_lease<L: WorkerIdLease + Sync + 'static>(&mut self, lease: Arc<L>) {
+        self.worker.lease = Some(lease);
+    }
+
+    /// Initialize the generator
+    ///
+    /// If a tick store is set, the generation resumes strictly after the persisted time, waited for by
+    /// `Sleep` and `WaitLastTick`. It returns an error, leaving the generator unchanged, if the current time is
+    /// behind it with `Immediate`, or further behind than the checkpoint interval and the drift allow,
+    /// i.e. the clock has been set backwards.
     pub fn init(&mut self, options: IdGeneratorOptions) -> Result<(), OptionError> {
         let instance_options: InstanceOptions = options.into();
         self.worker.init_options(instance_options)
     }
 
+    /// Initialize a new generator without waiting, `wait_for_startup` is to be called before generating ids
+    pub(crate) fn init_deferred(&mut self, options: IdGeneratorOptions) -> Result<(), OptionError> {
+        self.worker.prepare_options(options.into()).map(|_| ())
+    }
+
+    /// Wait for the startup strategy of a generator initialized with `init_deferred` since the given time
+    pub(crate) fn wait_for_startup(&self, since: Instant) {
+        self.worker.wait_for_startup(since)
+    }
+
     pub fn get_options(&self) -> IdGeneratorOptions {
         self.worker.get_options()
     }
@@ -31,22 +96,212 @@ impl CoreIdGenerator {
         self.worker.set_options(options)
     }
 
+    /// Get a unique id
+    ///
+    /// While the tick store fails to save, it retries until it succeeds. Use `try_next_id` to get the error instead.
+    ///
+    /// # Panics
+    ///
+    /// Panics with `GenerateError::LeaseLost` once the lease set with `set_lease` is lost, as no new lease
+    /// can be set while it holds the generator. `IdGenerator::next_id` waits for a new lease instead.
+    ///
+    /// Panics with `GenerateError::IdTooWide` with `IdWidth::Bits128`, use `next_u128` instead.
     pub fn next_id(&mut self) -> i64 {
-        self.worker.next_id()
+        retry(false, || self.worker.poll_id())
+    }
+
+    /// Get a unique id, or an error if no unique id can be generated
+    pub fn try_next_id(&mut self) -> Result<i64, GenerateError> {
+        self.worker.try_next_id()
     }
+
+    /// Get a unique id encoded in fixed width, so the strings sort in the order of the ids
+    ///
+    /// # Panics
+    ///
+    /// Like `next_id`, once the lease is lost or with `IdWidth::Bits128`.
+    pub fn next_id_string(&mut self, encoding: Encoding) -> String {
+        encoding.encode_fixed(self.next_u64())
+    }
+
+    /// Get a unique id of all the 64 bits with `IdWidth::Bits64`, the same bits as `next_id` otherwise
+    ///
+    /// # Panics
+    ///
+    /// Like `next_id`, once the lease is lost or with `IdWidth::Bits128`.
+    pub fn next_u64(&mut self) -> u64 {
+        self.next_id() as u64
+    }
+
+    /// Get a unique id of all the 64 bits, or an error if no unique id can be generated
+    pub fn try_next_u64(&mut self) -> Result<u64, GenerateError> {
+        self.worker.try_next_id().map(|id| id as u64)
+    }
+
+    /// Get a unique 128-bit id with `IdWidth::Bits128`, the 64-bit id widened otherwise
+    ///
+    /// The drift and turn back apply the same as the 64-bit ids.
+    ///
+    /// # Panics
+    ///
+    /// Like `next_id`, once the lease is lost.
+    pub fn next_u128(&mut self) -> u128 {
+        retry(false, || self.worker.poll_u128())
+    }
+
+    /// Get a unique 128-bit id, or an error if no unique id can be generated
+    pub fn try_next_u128(&mut self) -> Result<u128, GenerateError> {
+        self.worker.try_next_u128()
+    }
+
+    /// Get `n` unique ids at once
+    ///
+    /// # Panics
+    ///
+    /// Like `next_id`, once the lease is lost or with `IdWidth::Bits128`.
+    pub fn next_ids(&mut self, n: usize) -> Vec<i64> {
+        let mut ids = vec![0; n];
+        self.fill_ids(&mut ids);
+        ids
+    }
+
+    /// Fill the slice with unique ids
+    ///
+    /// Ids in the same millisecond are handed out as a contiguous range without reading the clock again.
+    ///
+    /// # Panics
+    ///
+    /// Like `next_id`, once the lease is lost or with `IdWidth::Bits128`.
+    pub fn fill_ids(&mut self, ids: &mut [i64]) {
+        let mut filled = 0;
+        while filled < ids.len() {
+            for id in retry(false, || self.worker.poll_id_range(ids.len() - filled)) {
+                ids[filled] = id;
+                filled += 1;
+            }
+        }
+    }
+
+    /// Reserve a block of `count` unique ids in advance
+    ///
+    /// Ids in the block will never be generated again by this generator.
+    ///
+    /// # Panics
+    ///
+    /// Like `next_id`, once the lease is lost or with `IdWidth::Bits128`.
+    pub fn lease_block(&mut self, count: usize) -> IdBlock {
+        let mut block = IdBlock::default();
+        while block.remaining() < count {
+            block.push(retry(false, || {
+                self.worker.poll_id_range(count - block.remaining())
+            }));
+        }
+        block
+    }
+
+    /// Get a unique id, unless the lease is lost or the tick store fails to save
+    pub(crate) fn poll_id(&mut self) -> Result<i64, GenerateError> {
+        self.worker.poll_id()
+    }
+
+    pub(crate) fn poll_u128(&mut self) -> Result<u128, GenerateError> {
+        self.worker.poll_u128()
+    }
+
+    pub(crate) fn poll_id_range(&mut self, max_len: usize) -> Result<Range<i64>, GenerateError> {
+        self.worker.poll_id_range(max_len)
+    }
+
+    /// How long and how fast ids can be generated with the current options
+    pub fn horizon(&self) -> Horizon {
+        let options: InstanceOptions = self.get_options().into();
+        options.horizon(self.worker.clock.now_millis())
+    }
+
+    /// Decode an id generated with the current options
+    pub fn decode(&self, id: i64) -> DecodedId {
+        decoder::decode(id, &self.get_options())
+    }
+}
+
+/// Retry until an id can be generated, for the infallible ways of generating ids
+///
+/// A lost lease is waited for to be replaced with `wait_for_lease`, and the other errors panic,
+/// since retrying does not change them.
+pub(crate) fn retry<T>(
+    wait_for_lease: bool,
+    mut poll: impl FnMut() -> Result<T, GenerateError>,
+) -> T {
+    loop {
+        match poll() {
+            Ok(value) => return value,
+            Err(GenerateError::LeaseLost(_)) if wait_for_lease => sleep(BLOCKED_RETRY_INTERVAL),
+            Err(GenerateError::TickStore(_)) => sleep(BLOCKED_RETRY_INTERVAL),
+            Err(e) => panic!("{}", e),
+        }
+    }
+}
+
+/// Check the worker id bit length alone
+pub(crate) fn check_worker_id_bit_len(worker_id_bit_len: u8) -> Result<(), OptionError> {
+    if worker_id_bit_len == 0 || worker_id_bit_len > 21 {
+        return Err(OptionError::InvalidWorkerIdBitLen(
+            "should have worker_id_bit_len in range [1, 21]".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+/// Check the worker id against the worker id bit length
+pub(crate) fn check_worker_id(worker_id: u32, worker_id_bit_len: u8) -> Result<(), OptionError> {
+    let mut max_worker_id_number = (1 << worker_id_bit_len) - 1;
+    if max_worker_id_number == 0 {
+        max_worker_id_number = 63;
+    }
+    if worker_id > max_worker_id_number {
+        return Err(OptionError::InvalidWorkerId(format!(
+            "should in range [0, {max_worker_id_number}]"
+        )));
+    }
+    Ok(())
+}
+
+/// Advance the state and return the next number of the SplitMix64 sequence
+pub(crate) fn splitmix64(state: &mut u64) -> u64 {
+    *state = state.wrapping_add(0x9e3779b97f4a7c15);
+    let mut z = *state;
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+    z ^ (z >> 31)
 }
 
+/// Bit length of the time tick of 128-bit ids
+pub(crate) const WIDE_TIMESTAMP_BIT_LEN: u8 = 48;
+
+/// Bit length of the node, i.e. datacenter id and worker id, of 128-bit ids
+pub(crate) const WIDE_NODE_BIT_LEN: u8 = 16;
+
+/// Average milliseconds in a Gregorian year
+const MILLIS_PER_YEAR: f64 = 365.2425 * 24.0 * 3600.0 * 1000.0;
+
 /// The options interact with the snowflake worker inside the CoreIdGenerator
 #[derive(Debug, Clone)]
-struct InstanceOptions {
+pub(crate) struct InstanceOptions {
     pub method: u8,
     pub base_time: i64,
+    pub time_unit: TimeUnit,
     pub worker_id: u32,
     pub worker_id_bit_len: u8,
+    pub datacenter_id: u32,
+    pub datacenter_id_bit_len: u8,
     pub seq_bit_len: u8,
     pub max_seq_num: u32,
     pub min_seq_num: u32,
     pub top_over_cost_count: u32,
+    pub min_horizon_years: u32,
+    pub startup_strategy: StartupStrategy,
+    pub id_width: IdWidth,
+    pub layout: Option<IdLayout>,
 }
 
 impl From<IdGeneratorOptions> for InstanceOptions {
@@ -54,14 +309,234 @@ impl From<IdGeneratorOptions> for InstanceOptions {
         InstanceOptions {
             method: options.method.unwrap_or(1),
             base_time: options.base_time.unwrap_or(1582136402000),
+            time_unit: options.time_unit.unwrap_or(TimeUnit::Millisecond),
             worker_id: options.worker_id.unwrap_or(0),
             worker_id_bit_len: options.worker_id_bit_len.unwrap_or(8),
+            datacenter_id: options.datacenter_id.unwrap_or(0),
+            datacenter_id_bit_len: options.datacenter_id_bit_len.unwrap_or(0),
             seq_bit_len: options.seq_bit_len.unwrap_or(8),
             max_seq_num: options.max_seq_num.unwrap_or(0),
             min_seq_num: options.min_seq_num.unwrap_or(5),
             top_over_cost_count: options.top_over_cost_count.unwrap_or(2000),
+            min_horizon_years: options.min_horizon_years.unwrap_or(0),
+            startup_strategy: options.startup_strategy.unwrap_or(StartupStrategy::Sleep),
+            id_width: options.id_width.unwrap_or(IdWidth::Bits63),
+            layout: options.layout,
+        }
+    }
+}
+
+impl From<InstanceOptions> for IdGeneratorOptions {
+    fn from(options: InstanceOptions) -> Self {
+        IdGeneratorOptions {
+            method: Some(options.method),
+            base_time: Some(options.base_time),
+            time_unit: Some(options.time_unit),
+            worker_id: Some(options.worker_id),
+            worker_id_bit_len: Some(options.worker_id_bit_len),
+            datacenter_id: Some(options.datacenter_id),
+            datacenter_id_bit_len: Some(options.datacenter_id_bit_len),
+            seq_bit_len: Some(options.seq_bit_len),
+            max_seq_num: Some(options.max_seq_num),
+            min_seq_num: Some(options.min_seq_num),
+            top_over_cost_count: Some(options.top_over_cost_count),
+            min_horizon_years: Some(options.min_horizon_years),
+            startup_strategy: Some(options.startup_strategy),
+            id_width: Some(options.id_width),
+            layout: options.layout,
+        }
+    }
+}
+
+impl InstanceOptions {
+    /// Check the options against the current time (in milliseconds), filling in the default values where needed
+    pub(crate) fn check(&mut self, now_millis: i64) -> Result<(), OptionError> {
+        // 0. Check method
+        if self.method != 1 && self.method != 2 {
+            return Err(OptionError::InvalidMethod);
+        }
+
+        // 1. Check base time
+        if self.base_time == 0 {
+            self.base_time = 1582136402000;
+        } else if self.base_time < 631123200000
+            || self.base_time > now_millis
+            || self.base_time % self.time_unit.millis() != 0
+        {
+            return Err(OptionError::InvalidBaseTime);
+        }
+
+        // 2. Check worker id bit length, along with datacenter id bit length, or the custom layout instead
+        if let Some(layout) = &self.layout {
+            if self.id_width == IdWidth::Bits128 {
+                return Err(OptionError::InvalidLayout(
+                    "should not be set for 128-bit ids".to_string(),
+                ));
+            }
+            layout.check(self.id_width.bit_len())?;
+            self.worker_id_bit_len = layout.position(|f| matches!(f, LayoutField::Worker(_))).1;
+            self.datacenter_id_bit_len = layout
+                .position(|f| matches!(f, LayoutField::Datacenter(_)))
+                .1;
+            self.seq_bit_len = layout.position(|f| matches!(f, LayoutField::Sequence(_))).1;
+        } else {
+            check_worker_id_bit_len(self.worker_id_bit_len)?;
+            let node_bit_len = self.datacenter_id_bit_len as u32 + self.worker_id_bit_len as u32;
+            if self.id_width == IdWidth::Bits128 {
+                if node_bit_len > WIDE_NODE_BIT_LEN as u32 {
+                    return Err(OptionError::BitLenOverflow(
+                        "should have datacenter_id_bit_len + worker_id_bit_len <= 16 for 128-bit ids"
+                            .to_string(),
+                    ));
+                }
+            } else if node_bit_len + self.seq_bit_len as u32 > 22 {
+                let message = match self.datacenter_id_bit_len {
+                    0 => "should have worker_id_bit_len + seq_bit_len <= 22",
+                    _ => {
+                        "should have datacenter_id_bit_len + worker_id_bit_len + seq_bit_len <= 22"
+                    }
+                };
+                return Err(OptionError::BitLenOverflow(message.to_string()));
+            }
+        }
+
+        // 3. Check worker id and datacenter id
+        if self.worker_id_bit_len == 0 {
+            if self.worker_id != 0 {
+                return Err(OptionError::InvalidWorkerId(
+                    "should be 0 without a worker field in the layout".to_string(),
+                ));
+            }
+        } else {
+            check_worker_id(self.worker_id, self.worker_id_bit_len)?;
+        }
+        let max_datacenter_id_number = (1 << self.datacenter_id_bit_len) - 1;
+        if self.datacenter_id > max_datacenter_id_number {
+            return Err(OptionError::InvalidDatacenterId(format!(
+                "should in range [0, {max_datacenter_id_number}]"
+            )));
+        }
+
+        // 4. Check sequence bit length
+        let max_seq_bit_len = match self.id_width {
+            IdWidth::Bits128 => 31,
+            _ => 21,
+        };
+        if self.seq_bit_len < 2 || self.seq_bit_len > max_seq_bit_len {
+            return Err(OptionError::InvalidSeqBitLen(format!(
+                "should have seq_bit_len in range [2, {max_seq_bit_len}]"
+            )));
+        }
+
+        // 5. Check max sequence number
+        let mut max_seq_number = (1 << self.seq_bit_len) - 1;
+        if max_seq_number == 0 {
+            max_seq_number = 63;
+        }
+        if self.max_seq_num > max_seq_number {
+            return Err(OptionError::InvalidMaxSeqNum(format!(
+                "should in range [1, {max_seq_number}]"
+            )));
+        } else if self.max_seq_num == 0 {
+            self.max_seq_num = max_seq_number
+        }
+
+        // 6. Checkk min sequence number
+        if self.min_seq_num < 5 || self.min_seq_num > max_seq_number {
+            return Err(OptionError::InvalidMinSeqNum(format!(
+                "should in range [5, {max_seq_number}]"
+            )));
+        }
+
+        // 7. Check top over cost count
+        if self.top_over_cost_count == 0 {
+            self.top_over_cost_count = 2000;
+        }
+
+        // 8. Check horizon
+        if self.min_horizon_years > 0 {
+            let remaining_years = self.horizon(now_millis).remaining_years;
+            if remaining_years < self.min_horizon_years as f64 {
+                return Err(OptionError::InsufficientHorizon(format!(
+                    "should have at least {} years left, but only {remaining_years:.1} years",
+                    self.min_horizon_years
+                )));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Compute the horizon of the (checked) options at the current time (in milliseconds)
+    pub(crate) fn horizon(&self, now_millis: i64) -> Horizon {
+        let max_time_tick = self.shifts().max_time_tick;
+        let unit = self.time_unit.millis();
+        let last_timestamp = self
+            .base_time
+            .saturating_add(max_time_tick.saturating_mul(unit));
+        let ids_per_tick = (self.max_seq_num + 1).saturating_sub(self.min_seq_num) as u64;
+        // Drift is a one-off burst borrowing ticks ahead, not a sustained rate
+        let ticks_per_second = 1000 / unit as u64;
+        Horizon {
+            last_timestamp,
+            remaining_years: (last_timestamp - now_millis) as f64 / MILLIS_PER_YEAR,
+            ids_per_tick,
+            ids_per_second: ids_per_tick * ticks_per_second,
+            max_burst_ids: match self.method {
+                1 => ids_per_tick * self.top_over_cost_count as u64,
+                _ => 0,
+            },
+        }
+    }
+
+    /// The custom layout, or the default one of the bit lengths
+    pub(crate) fn layout(&self) -> IdLayout {
+        match &self.layout {
+            Some(layout) => layout.clone(),
+            None => IdLayout::standard(
+                self.id_width.bit_len(),
+                self.datacenter_id_bit_len,
+                self.worker_id_bit_len,
+                self.seq_bit_len,
+            ),
         }
     }
+
+    /// Positions of the fields of the (checked) options in the ids
+    pub(crate) fn shifts(&self) -> LayoutShifts {
+        if self.id_width == IdWidth::Bits128 {
+            // 128-bit ids are composed by the worker, only the time tick is bounded here
+            return LayoutShifts {
+                max_time_tick: (1 << WIDE_TIMESTAMP_BIT_LEN) - 1,
+                ..Default::default()
+            };
+        }
+        let layout = self.layout();
+        let (timestamp_shift, timestamp_bit_len) =
+            layout.position(|f| matches!(f, LayoutField::Timestamp(_)));
+        let (seq_shift, _) = layout.position(|f| matches!(f, LayoutField::Sequence(_)));
+        let (worker_shift, _) = layout.position(|f| matches!(f, LayoutField::Worker(_)));
+        let (datacenter_shift, _) = layout.position(|f| matches!(f, LayoutField::Datacenter(_)));
+        LayoutShifts {
+            timestamp_shift,
+            // The time tick of 64-bit ids may take the sign bit
+            max_time_tick: ((1_u64 << timestamp_bit_len) - 1) as i64,
+            seq_shift,
+            node_part: ((self.datacenter_id as i64) << datacenter_shift)
+                + ((self.worker_id as i64) << worker_shift)
+                + layout.custom_part(),
+        }
+    }
+}
+
+/// Positions of the fields in the ids, compiled from the layout
+#[derive(Debug, Clone, Copy, Default)]
+pub(crate) struct LayoutShifts {
+    pub timestamp_shift: u8,
+    pub max_time_tick: i64,
+    pub seq_shift: u8,
+    /// All the fields other than the time tick and the sequence, i.e. datacenter id, worker id and custom fields
+    pub node_part: i64,
 }
 
 impl Default for InstanceOptions {
@@ -69,12 +544,19 @@ impl Default for InstanceOptions {
         InstanceOptions {
             method: 1,
             base_time: 1582136402000,
+            time_unit: TimeUnit::Millisecond,
             worker_id: 0,
             worker_id_bit_len: 8,
+            datacenter_id: 0,
+            datacenter_id_bit_len: 0,
             seq_bit_len: 8,
             max_seq_num: 0,
             min_seq_num: 5,
             top_over_cost_count: 2000,
+            min_horizon_years: 0,
+            startup_strategy: StartupStrategy::Sleep,
+            id_width: IdWidth::Bits63,
+            layout: None,
         }
     }
 }
@@ -87,10 +569,16 @@ struct SnowFlake {
     method: u8,
     /// Base time
     base_time: i64,
+    /// Unit of the time ticks
+    time_unit: TimeUnit,
     /// Machine code
     worker_id: u32,
     /// Length of machine code length(in bit)
     worker_id_bit_len: u8,
+    /// Datacenter code
+    datacenter_id: u32,
+    /// Length of datacenter code(in bit)
+    datacenter_id_bit_len: u8,
     /// Length of the self-increment sequence(int bit)
     seq_bit_len: u8,
     /// The max sequence number(included)
@@ -99,17 +587,47 @@ struct SnowFlake {
     min_seq_num: u32,
     /// The max shift count
     top_over_cost_count: u32,
+    /// The min years left before the time tick overflows
+    min_horizon_years: u32,
+    /// how to avoid overlapping the ids generated before initializing or setting options
+    startup_strategy: StartupStrategy,
+    /// Whether the startup strategy has been applied to the current worker
+    started: bool,
+    /// Custom bit layout of the ids
+    layout: Option<IdLayout>,
+    /// bit width of the ids, see `IdWidth`
+    id_width: IdWidth,
+    /// Source of the current time
+    clock: Box<dyn Clock>,
+    /// Storage of the time ids are generated up to
+    tick_store: Option<Box<dyn TickStore>>,
+    /// How far ahead (in milliseconds) the persisted time is saved
+    checkpoint_interval: i64,
+    /// The persisted time (in milliseconds), ids at or after it should not be generated before saving a new one
+    checkpoint_timestamp: i64,
+    /// Lease of the worker id, ids are only generated while it is valid
+    lease: Option<Arc<dyn WorkerIdLease + Sync>>,
 
     // inner variables
     timestamp_shift: u8,
+    max_time_tick: i64,
+    seq_shift: u8,
+    /// All the fields of the ids other than the time tick and the sequence
+    node_part: i64,
     current_seq_number: u32,
     last_time_tick: i64,
     turn_back_time_tick: i64,
     turn_back_index: u8,
+    /// Range of time ticks (lowest, highest) used by the latest turn back of each index
+    turn_back_ranges: [(i64, i64); 4],
+    /// Range of time ticks used by the previous turn back of the current index
+    turn_back_guard: (i64, i64),
     is_over_cost: bool,
     over_cost_count_in_one_term: u32,
     gen_count_in_one_term: u32,
     term_index: u32,
+    /// State of the random bits of 128-bit ids
+    random_state: u64,
 }
 
 impl Default for SnowFlake {
@@ -118,45 +636,117 @@ impl Default for SnowFlake {
         let mut worker = SnowFlake {
             method: 1,
             base_time: 1582136402000,
+            time_unit: TimeUnit::Millisecond,
             worker_id_bit_len: 0,
             worker_id: 0,
+            datacenter_id: 0,
+            datacenter_id_bit_len: 0,
             seq_bit_len: 0,
             max_seq_num: 0,
             min_seq_num: 0,
             top_over_cost_count: 0,
+            min_horizon_years: 0,
+            startup_strategy: StartupStrategy::Sleep,
+            started: false,
+            layout: None,
+            id_width: IdWidth::Bits63,
+            clock: Box::new(SystemClock),
+            tick_store: None,
+            checkpoint_interval: 0,
+            checkpoint_timestamp: i64::MAX,
+            lease: None,
             timestamp_shift: 0,
+            max_time_tick: 0,
+            seq_shift: 0,
+            node_part: 0,
             current_seq_number: 0,
 
             last_time_tick: 0,
             turn_back_time_tick: 0,
             turn_back_index: 0,
+            turn_back_ranges: [(1, 0); 4],
+            turn_back_guard: (1, 0),
             is_over_cost: false,
             over_cost_count_in_one_term: 0,
             gen_count_in_one_term: 0,
             term_index: 0,
+            random_state: RandomState::new().build_hasher().finish(),
         };
-        worker.init_options(options).unwrap();
+        // Nothing has been generated by a new worker, the startup strategy applies when it is initialized
+        let mut options = options;
+        worker.check_options(&mut options).unwrap();
+        worker.set_instance_options(options);
         worker
     }
 }
 
 impl SnowFlake {
-    pub fn init_options(&mut self, mut options: InstanceOptions) -> Result<(), OptionError> {
+    pub fn init_options(&mut self, options: InstanceOptions) -> Result<(), OptionError> {
+        let since = Instant::now();
+        if self.prepare_options(options)? {
+            self.wait_for_startup(since);
+        }
+        Ok(())
+    }
+
+    /// Initialize with the options without waiting, `true` if the startup strategy is still to be waited for
+    pub fn prepare_options(&mut self, mut options: InstanceOptions) -> Result<bool, OptionError> {
         self.check_options(&mut options)?;
+        let restart = self.restarts(&options);
+        // A running generator already knows the ticks it has persisted
+        let persisted = match &self.tick_store {
+            Some(store) if restart => store
+                .load()
+                .map_err(|e| OptionError::TickStore(e.to_string()))?,
+            _ => None,
+        };
+        // Validate against the persisted time with the new options before changing anything
+        let unit = options.time_unit.millis();
+        let persisted = persisted.map(|timestamp| {
+            // The time tick the persisted time falls in may have been used
+            let time_tick = (timestamp - options.base_time + unit - 1).div_euclid(unit);
+            (timestamp, time_tick)
+        });
+        if let Some((_, persisted_time_tick)) = persisted {
+            let current_time_tick = (self.clock.now_millis() - options.base_time).div_euclid(unit);
+            let behind = (persisted_time_tick - current_time_tick) * unit;
+            // The persisted time is the checkpoint interval ahead of the ticks used, which may be borrowed by drift
+            let drift_ticks = match options.method {
+                1 => options.top_over_cost_count as i64,
+                _ => 0,
+            };
+            let max_behind = self.checkpoint_interval.max(1) + (drift_ticks + 1) * unit;
+            let waits = options.startup_strategy != StartupStrategy::Immediate;
+            if current_time_tick <= persisted_time_tick && (!waits || behind > max_behind) {
+                return Err(OptionError::ClockBehindTickStore(behind));
+            }
+        }
         self.set_instance_options(options);
-        Ok(())
+        if let Some((timestamp, persisted_time_tick)) = persisted {
+            self.last_time_tick = self.last_time_tick.max(persisted_time_tick);
+            self.checkpoint_timestamp = timestamp;
+        }
+        self.started |= restart;
+        Ok(restart)
     }
 
     pub fn get_options(&self) -> IdGeneratorOptions {
         IdGeneratorOptions {
             method: Some(self.method),
             base_time: Some(self.base_time),
+            time_unit: Some(self.time_unit),
             worker_id: Some(self.worker_id),
             worker_id_bit_len: Some(self.worker_id_bit_len),
+            datacenter_id: Some(self.datacenter_id),
+            datacenter_id_bit_len: Some(self.datacenter_id_bit_len),
             seq_bit_len: Some(self.seq_bit_len),
             max_seq_num: Some(self.max_seq_num),
             min_seq_num: Some(self.min_seq_num),
             top_over_cost_count: Some(self.top_over_cost_count),
+            min_horizon_years: Some(self.min_horizon_years),
+            startup_strategy: Some(self.startup_strategy),
+            id_width: Some(self.id_width),
+            layout: self.layout.clone(),
         }
     }
 
@@ -164,109 +754,253 @@ impl SnowFlake {
         let mut instance_options: InstanceOptions = InstanceOptions {
             method: options.method.unwrap_or(self.method),
             base_time: options.base_time.unwrap_or(self.base_time),
+            time_unit: options.time_unit.unwrap_or(self.time_unit),
             worker_id: options.worker_id.unwrap_or(self.worker_id),
             worker_id_bit_len: options.worker_id_bit_len.unwrap_or(self.worker_id_bit_len),
+            datacenter_id: options.datacenter_id.unwrap_or(self.datacenter_id),
+            datacenter_id_bit_len: options
+                .datacenter_id_bit_len
+                .unwrap_or(self.datacenter_id_bit_len),
             seq_bit_len: options.seq_bit_len.unwrap_or(self.seq_bit_len),
             max_seq_num: options.max_seq_num.unwrap_or(self.max_seq_num),
             min_seq_num: options.min_seq_num.unwrap_or(self.min_seq_num),
             top_over_cost_count: options
                 .top_over_cost_count
                 .unwrap_or(self.top_over_cost_count),
+            min_horizon_years: options.min_horizon_years.unwrap_or(self.min_horizon_years),
+            startup_strategy: options.startup_strategy.unwrap_or(self.startup_strategy),
+            id_width: options.id_width.unwrap_or(self.id_width),
+            layout: options.layout.or_else(|| self.layout.clone()),
         };
+        let since = Instant::now();
         self.check_options(&mut instance_options)?;
+        let restart = self.restarts(&instance_options);
         self.set_instance_options(instance_options);
+        if restart {
+            self.started = true;
+            self.wait_for_startup(since);
+        }
         Ok(())
     }
 
-    pub fn next_id(&mut self) -> i64 {
-        if self.is_over_cost {
-            self.next_over_cost_id()
-        } else {
-            self.next_normal_id()
-        }
+    pub fn poll_id(&mut self) -> Result<i64, GenerateError> {
+        self.check_id_width()?;
+        let (time_tick, seq_number) = self.poll_tick_and_seq()?;
+        Ok(self.calc_id(time_tick, seq_number))
     }
 
-    fn check_options(&self, options: &mut InstanceOptions) -> Result<(), OptionError> {
-        // 1. Check base time
-        if options.base_time == 0 {
-            options.base_time = 1582136402000;
-        } else if options.base_time < 631123200000
-            || options.base_time > Utc::now().timestamp_millis()
-        {
-            return Err(OptionError::InvalidBaseTime);
+    pub fn try_next_id(&mut self) -> Result<i64, GenerateError> {
+        self.check_id_width()?;
+        let (time_tick, seq_number) = self.try_next_tick_and_seq()?;
+        Ok(self.calc_id(time_tick, seq_number))
+    }
+
+    pub fn poll_u128(&mut self) -> Result<u128, GenerateError> {
+        let (time_tick, seq_number) = self.poll_tick_and_seq()?;
+        Ok(self.calc_u128(time_tick, seq_number))
+    }
+
+    pub fn try_next_u128(&mut self) -> Result<u128, GenerateError> {
+        let (time_tick, seq_number) = self.try_next_tick_and_seq()?;
+        Ok(self.calc_u128(time_tick, seq_number))
+    }
+
+    /// 128-bit ids are only generated with `poll_u128` and `try_next_u128`
+    fn check_id_width(&self) -> Result<(), GenerateError> {
+        if self.id_width == IdWidth::Bits128 {
+            return Err(GenerateError::IdTooWide);
         }
+        Ok(())
+    }
+
+    /// Take the time tick and sequence number of the next id, shared by the ids of all the widths
+    ///
+    /// The only errors are the ones the infallible ways of generating ids retry on:
+    /// `GenerateError::LeaseLost` and `GenerateError::TickStore`.
+    fn poll_tick_and_seq(&mut self) -> Result<(i64, u32), GenerateError> {
+        self.check_lease()?;
+        let current_time_tick = self.get_current_time_tick();
+        self.save_checkpoint(current_time_tick)?;
+        Ok(self.next_id_at(current_time_tick))
+    }
+
+    fn try_next_tick_and_seq(&mut self) -> Result<(i64, u32), GenerateError> {
+        let current_time_tick = self.get_current_time_tick();
+        self.check_next_id_at(current_time_tick)?;
+        self.save_checkpoint(current_time_tick)?;
+        Ok(self.next_id_at(current_time_tick))
+    }
 
-        // 2. Check worker id bit length
-        if options.worker_id_bit_len == 0 {
-            return Err(OptionError::InvalidWorkerIdBitLen(
-                "should have worker_id_bit_len in range [1, 21]".to_string(),
-            ));
-        } else if options.seq_bit_len + options.worker_id_bit_len > 22 {
-            return Err(OptionError::BitLenOverflow(
-                "should have worker_id_bit_len + seq_bit_len <= 22".to_string(),
-            ));
+    fn check_lease(&self) -> Result<(), GenerateError> {
+        match &self.lease {
+            Some(lease) if !lease.is_valid() => Err(GenerateError::LeaseLost(lease.worker_id())),
+            _ => Ok(()),
         }
+    }
+
+    fn save_checkpoint(&mut self, current_time_tick: i64) -> Result<(), GenerateError> {
+        self.checkpoint(current_time_tick)
+            .map_err(|e| GenerateError::TickStore(e.to_string()))
+    }
 
-        // 3. Check worker id
-        let mut max_worker_id_number = (1 << options.worker_id_bit_len) - 1;
-        if max_worker_id_number == 0 {
-            max_worker_id_number = 63;
+    /// Save a new high-water mark before the next id at the given time tick may reach the persisted one
+    fn checkpoint(&mut self, current_time_tick: i64) -> std::io::Result<()> {
+        // The time tick used will never exceed the larger one of the current and the next of the last
+        let next_timestamp = self.tick_timestamp(current_time_tick.max(self.last_time_tick + 1));
+        if next_timestamp < self.checkpoint_timestamp {
+            return Ok(());
         }
-        if options.worker_id > max_worker_id_number {
-            return Err(OptionError::InvalidWorkerId(format!(
-                "should in range [0, {max_worker_id_number}]"
-            )));
+        if let Some(store) = &self.tick_store {
+            let timestamp = next_timestamp + self.checkpoint_interval.max(1);
+            store.save(timestamp)?;
+            self.checkpoint_timestamp = timestamp;
         }
+        Ok(())
+    }
 
-        // 4. Check sequence bit length
-        if options.seq_bit_len < 2 || options.seq_bit_len > 21 {
-            return Err(OptionError::InvalidSeqBitLen(
-                "should have seq_bit_len in range [2, 21]".to_string(),
-            ));
+    fn next_id_at(&mut self, current_time_tick: i64) -> (i64, u32) {
+        if self.method == 2 {
+            return self.next_classic_id(current_time_tick);
         }
-
-        // 5. Check max sequence number
-        let mut max_seq_number = (1 << options.seq_bit_len) - 1;
-        if max_seq_number == 0 {
-            max_seq_number = 63;
+        if self.is_over_cost {
+            self.next_over_cost_id(current_time_tick)
+        } else {
+            self.next_normal_id(current_time_tick)
         }
-        if options.max_seq_num > max_seq_number {
-            return Err(OptionError::InvalidMaxSeqNum(format!(
-                "should in range [1, {max_seq_number}]"
-            )));
-        } else if options.max_seq_num == 0 {
-            options.max_seq_num = max_seq_number
+    }
+
+    /// Check whether a unique id can be generated at the given time tick
+    fn check_next_id_at(&self, current_time_tick: i64) -> Result<(), GenerateError> {
+        self.check_lease()?;
+
+        // The time tick used will never exceed the larger one of the current and the next of the last
+        if current_time_tick.max(self.last_time_tick + 1) > self.max_time_tick {
+            return Err(GenerateError::TimeTickOverflow(self.max_time_tick));
         }
 
-        // 6. Checkk min sequence number
-        if options.min_seq_num < 5 || options.min_seq_num > max_seq_number {
-            return Err(OptionError::InvalidMinSeqNum(format!(
-                "should in range [5, {max_seq_number}]"
-            )));
+        if self.method == 1 && !self.is_over_cost && current_time_tick < self.last_time_tick {
+            let (use_time_tick, guard) = if self.turn_back_time_tick < 1 {
+                let index = self.turn_back_index as usize % 4;
+                (self.last_time_tick - 1, self.turn_back_ranges[index])
+            } else {
+                (self.turn_back_time_tick, self.turn_back_guard)
+            };
+            // The reserved turn back sequence of this index has been used in this time tick before
+            if use_time_tick < 1 || (guard.0 <= use_time_tick && use_time_tick <= guard.1) {
+                return Err(GenerateError::ClockMovedBackwards(
+                    self.last_time_tick - current_time_tick,
+                ));
+            }
         }
+        Ok(())
+    }
 
-        // 7. Check top over cost count
-        if options.top_over_cost_count == 0 {
-            options.top_over_cost_count = 2000;
+    /// Get a range of consecutive unique ids starting from the next id, at most `max_len` long.
+    ///
+    /// The range never goes beyond the sequence left in the current time tick.
+    pub fn poll_id_range(&mut self, max_len: usize) -> Result<Range<i64>, GenerateError> {
+        let first_id = self.poll_id()?;
+        // Ids taken from the reserved turn back sequence are not consecutive,
+        // neither are the ids of a layout where the sequence is not the lowest field
+        let seq = (first_id >> self.seq_shift) & ((1 << self.seq_bit_len) - 1);
+        if max_len <= 1 || seq < self.min_seq_num as i64 || self.seq_shift != 0 {
+            return Ok(first_id..first_id + 1);
         }
+        let left = (self.max_seq_num + 1).saturating_sub(self.current_seq_number);
+        let extra = left.min((max_len - 1) as u32);
+        self.current_seq_number += extra;
+        if self.is_over_cost {
+            self.gen_count_in_one_term += extra;
+        }
+        Ok(first_id..first_id + 1 + extra as i64)
+    }
 
-        Ok(())
+    fn check_options(&self, options: &mut InstanceOptions) -> Result<(), OptionError> {
+        options.check(self.clock.now_millis())
     }
 
     fn set_instance_options(&mut self, options: InstanceOptions) {
+        // Keep the time ticks generated before pointing to the same time under the new base time and time unit
+        let (unit, new_unit) = (self.time_unit.millis(), options.time_unit.millis());
+        if self.base_time != options.base_time || unit != new_unit {
+            let (base_time, new_base_time) = (self.base_time, options.base_time);
+            // The new time ticks the first and the last millisecond of an old time tick fall in
+            let first = |time_tick: i64| {
+                (base_time + time_tick * unit - new_base_time).div_euclid(new_unit)
+            };
+            let last = |time_tick: i64| {
+                (base_time + (time_tick + 1) * unit - 1 - new_base_time).div_euclid(new_unit)
+            };
+            let range = |(lowest, highest): (i64, i64)| match lowest <= highest {
+                true => (first(lowest), last(highest)),
+                false => (lowest, highest),
+            };
+            self.last_time_tick = last(self.last_time_tick);
+            if self.turn_back_time_tick > 0 {
+                self.turn_back_time_tick = first(self.turn_back_time_tick);
+            }
+            self.turn_back_ranges = self.turn_back_ranges.map(range);
+            self.turn_back_guard = range(self.turn_back_guard);
+        }
+
+        let shifts = options.shifts();
+        self.timestamp_shift = shifts.timestamp_shift;
+        self.max_time_tick = shifts.max_time_tick;
+        self.seq_shift = shifts.seq_shift;
+        self.node_part = shifts.node_part;
         self.method = options.method;
         self.base_time = options.base_time;
+        self.time_unit = options.time_unit;
         self.worker_id_bit_len = options.worker_id_bit_len;
         self.worker_id = options.worker_id;
+        self.datacenter_id = options.datacenter_id;
+        self.datacenter_id_bit_len = options.datacenter_id_bit_len;
         self.seq_bit_len = options.seq_bit_len;
         self.max_seq_num = options.max_seq_num;
         self.min_seq_num = options.min_seq_num;
         self.top_over_cost_count = options.top_over_cost_count;
+        self.min_horizon_years = options.min_horizon_years;
+        self.startup_strategy = options.startup_strategy;
+        self.id_width = options.id_width;
+        self.layout = options.layout;
 
-        self.timestamp_shift = self.worker_id_bit_len + self.seq_bit_len;
-        self.current_seq_number = self.min_seq_num;
-        if self.method == 1 {
-            sleep(std::time::Duration::from_millis(500));
+        // Keep generating after the ids generated before in the last time tick
+        self.current_seq_number = self.current_seq_number.max(self.min_seq_num);
+    }
+
+    /// Whether the startup strategy applies to the options
+    ///
+    /// A generator which keeps running as the same worker knows the ids it has generated,
+    /// so only a new generator, or one switching to another worker, has to avoid the ids generated by others.
+    fn restarts(&self, options: &InstanceOptions) -> bool {
+        !self.started
+            || self.worker_id != options.worker_id
+            || self.datacenter_id != options.datacenter_id
+    }
+
+    /// Avoid overlapping the ids generated before by others, according to the startup strategy
+    ///
+    /// The sleep is counted from `since`, so the waits of generators started together overlap.
+    pub fn wait_for_startup(&self, since: Instant) {
+        match self.startup_strategy {
+            StartupStrategy::Sleep => match self.method {
+                1 => {
+                    sleep(Duration::from_millis(500).saturating_sub(since.elapsed()));
+                    // The ids generated before by this worker reach up to the persisted time tick
+                    self.wait_past_tick(self.last_time_tick);
+                }
+                // Classic snowflake never borrows future ticks, the ids generated before are all in the past ones
+                _ => self.wait_past_tick(self.get_current_time_tick().max(self.last_time_tick)),
+            },
+            StartupStrategy::WaitLastTick => self.wait_past_tick(self.last_time_tick),
+            StartupStrategy::Immediate => {}
+        }
+    }
+
+    /// Wait until the current time tick is after the given one
+    fn wait_past_tick(&self, time_tick: i64) {
+        while self.get_current_time_tick() <= time_tick {
+            self.sleep_until_tick(time_tick + 1);
         }
     }
 
@@ -282,9 +1016,7 @@ impl SnowFlake {
 
     fn end_turn_back_action(&self, _use_time_tick: i64) {}
 
-    fn next_over_cost_id(&mut self) -> i64 {
-        let current_time_tick = self.get_current_time_tick();
-
+    fn next_over_cost_id(&mut self, current_time_tick: i64) -> (i64, u32) {
         if current_time_tick > self.last_time_tick {
             self.end_over_cost_action(current_time_tick);
             self.last_time_tick = current_time_tick;
@@ -292,7 +1024,7 @@ impl SnowFlake {
             self.is_over_cost = false;
             self.over_cost_count_in_one_term = 0;
             self.gen_count_in_one_term = 0;
-            self.calc_id(self.last_time_tick)
+            self.take_seq(self.last_time_tick)
         } else if self.over_cost_count_in_one_term >= self.top_over_cost_count {
             self.end_over_cost_action(current_time_tick);
             self.last_time_tick = self.get_next_time_tick();
@@ -300,23 +1032,21 @@ impl SnowFlake {
             self.is_over_cost = false;
             self.over_cost_count_in_one_term = 0;
             self.gen_count_in_one_term = 0;
-            self.calc_id(self.last_time_tick)
+            self.take_seq(self.last_time_tick)
         } else if self.current_seq_number > self.max_seq_num {
             self.last_time_tick += 1;
             self.current_seq_number = self.min_seq_num;
             self.is_over_cost = true;
             self.over_cost_count_in_one_term += 1;
             self.gen_count_in_one_term += 1;
-            self.calc_id(self.last_time_tick)
+            self.take_seq(self.last_time_tick)
         } else {
             self.gen_count_in_one_term += 1;
-            self.calc_id(self.last_time_tick)
+            self.take_seq(self.last_time_tick)
         }
     }
 
-    fn next_normal_id(&mut self) -> i64 {
-        let current_time_tick = self.get_current_time_tick();
-
+    fn next_normal_id(&mut self, current_time_tick: i64) -> (i64, u32) {
         if current_time_tick < self.last_time_tick {
             if self.turn_back_time_tick < 1 {
                 self.turn_back_time_tick = self.last_time_tick - 1;
@@ -326,9 +1056,12 @@ impl SnowFlake {
                 if self.turn_back_index > 4 {
                     self.turn_back_index = 1;
                 }
+                let index = self.turn_back_index as usize - 1;
+                self.turn_back_guard = self.turn_back_ranges[index];
+                self.turn_back_ranges[index] = (self.turn_back_time_tick, self.turn_back_time_tick);
                 self.begin_turn_back_action(self.turn_back_time_tick);
             }
-            return self.calc_turn_back_id(self.turn_back_time_tick);
+            return self.take_turn_back_seq(self.turn_back_time_tick);
         }
 
         // If the time is caught up, reset _turn_back_time_tick to zero
@@ -340,7 +1073,7 @@ impl SnowFlake {
         if current_time_tick > self.last_time_tick {
             self.last_time_tick = current_time_tick;
             self.current_seq_number = self.min_seq_num;
-            self.calc_id(self.last_time_tick)
+            self.take_seq(self.last_time_tick)
         } else if self.current_seq_number > self.max_seq_num {
             self.begin_over_cost_action(current_time_tick);
             self.term_index += 1;
@@ -349,37 +1082,91 @@ impl SnowFlake {
             self.is_over_cost = true;
             self.over_cost_count_in_one_term = 1;
             self.gen_count_in_one_term = 1;
-            self.calc_id(self.last_time_tick)
+            self.take_seq(self.last_time_tick)
         } else {
-            self.calc_id(self.last_time_tick)
+            self.take_seq(self.last_time_tick)
+        }
+    }
+
+    fn next_classic_id(&mut self, mut current_time_tick: i64) -> (i64, u32) {
+        // Never borrow ticks from the future: if the time turned back, wait until it catches up
+        while current_time_tick < self.last_time_tick {
+            self.sleep_until_tick(self.last_time_tick);
+            current_time_tick = self.get_current_time_tick();
+        }
+
+        if current_time_tick > self.last_time_tick {
+            self.last_time_tick = current_time_tick;
+            self.current_seq_number = self.min_seq_num;
+        } else if self.current_seq_number > self.max_seq_num {
+            self.last_time_tick = self.get_next_time_tick();
+            self.current_seq_number = self.min_seq_num;
         }
+        self.take_seq(self.last_time_tick)
     }
 
-    fn calc_id(&mut self, use_time_tick: i64) -> i64 {
-        let result = (use_time_tick << self.timestamp_shift)
-            + (self.worker_id << self.seq_bit_len) as i64
-            + (self.current_seq_number) as i64;
+    fn take_seq(&mut self, use_time_tick: i64) -> (i64, u32) {
+        let seq_number = self.current_seq_number;
         self.current_seq_number += 1;
-        result
+        (use_time_tick, seq_number)
     }
 
-    fn calc_turn_back_id(&mut self, use_time_tick: i64) -> i64 {
-        let result = (use_time_tick << self.timestamp_shift)
-            + (self.worker_id << self.seq_bit_len) as i64
-            + (self.turn_back_index) as i64;
+    fn take_turn_back_seq(&mut self, use_time_tick: i64) -> (i64, u32) {
+        self.turn_back_ranges[self.turn_back_index as usize - 1].0 = use_time_tick;
         self.turn_back_time_tick -= 1;
-        result
+        (use_time_tick, self.turn_back_index as u32)
+    }
+
+    /// Compose a 64-bit id, whose time tick may take the sign bit with `IdWidth::Bits64`
+    fn calc_id(&self, time_tick: i64, seq_number: u32) -> i64 {
+        (time_tick << self.timestamp_shift)
+            + self.node_part
+            + ((seq_number as i64) << self.seq_shift)
+    }
+
+    /// Compose a 128-bit id, or widen the 64-bit one unless `IdWidth::Bits128`
+    fn calc_u128(&mut self, time_tick: i64, seq_number: u32) -> u128 {
+        if self.id_width != IdWidth::Bits128 {
+            return self.calc_id(time_tick, seq_number) as u64 as u128;
+        }
+        let node =
+            ((self.datacenter_id as u128) << self.worker_id_bit_len) + self.worker_id as u128;
+        let random_bit_len = 64 - self.seq_bit_len;
+        ((time_tick as u128) << (128 - WIDE_TIMESTAMP_BIT_LEN))
+            + (node << 64)
+            + ((seq_number as u128) << random_bit_len)
+            + (self.next_random() >> self.seq_bit_len) as u128
+    }
+
+    /// Random bits of 128-bit ids
+    fn next_random(&mut self) -> u64 {
+        splitmix64(&mut self.random_state)
     }
 
     fn get_current_time_tick(&self) -> i64 {
-        Utc::now().timestamp_millis() - self.base_time
+        (self.clock.now_millis() - self.base_time).div_euclid(self.time_unit.millis())
     }
 
     fn get_next_time_tick(&self) -> i64 {
         let mut temp_time_ticker = self.get_current_time_tick();
         while temp_time_ticker <= self.last_time_tick {
+            // Spin for the next millisecond, but sleep for the coarser time units
+            if self.time_unit != TimeUnit::Millisecond {
+                self.sleep_until_tick(self.last_time_tick + 1);
+            }
             temp_time_ticker = self.get_current_time_tick();
         }
         temp_time_ticker
     }
+
+    /// Start of the time tick, in milliseconds since the Unix epoch
+    fn tick_timestamp(&self, time_tick: i64) -> i64 {
+        self.base_time + time_tick * self.time_unit.millis()
+    }
+
+    /// Sleep until the time tick begins, at least 1 millisecond
+    fn sleep_until_tick(&self, time_tick: i64) {
+        let millis = self.tick_timestamp(time_tick) - self.clock.now_millis();
+        sleep(Duration::from_millis(millis.max(1) as u64));
+    }
 }
@@ -0,0 +1,189 @@
+//! # Handle
+//!
+//! An owned, cloneable handle of a generator.
+//!
+//! Unlike the process-wide `IdInstance` and `IdVecInstance`, every `IdGenerator` created with `IdGenerator::new`
+//! is independent, so it can be owned by a library or passed around with dependency injection.
+//! Clones of a handle share the same generator.
+
+use parking_lot::Mutex;
+use std::sync::Arc;
+
+use crate::generator::retry;
+use crate::{
+    CoreIdGenerator, DecodedId, Encoding, GenerateError, Horizon, IdBlock, IdGeneratorOptions,
+    OptionError, WorkerIdLease,
+};
+
+/// Cloneable handle of a generator, clones share the same generator
+#[derive(Debug, Clone, Default)]
+pub struct IdGenerator {
+    inner: Arc<Mutex<CoreIdGenerator>>,
+    panic_on_lease_loss: bool,
+}
+
+impl IdGenerator {
+    /// Create a generator with the given options
+    ///
+    /// Options not set will be given the default value.
+    pub fn new(options: IdGeneratorOptions) -> Result<Self, OptionError> {
+        let mut generator = CoreIdGenerator::default();
+        generator.init(options)?;
+        Ok(generator.into())
+    }
+
+    /// Initialize the generator
+    pub fn init(&self, options: IdGeneratorOptions) -> Result<(), OptionError> {
+        self.inner.lock().init(options)
+    }
+
+    /// Only generate ids while the lease of the worker id is valid, see `CoreIdGenerator::set_lease`
+    pub fn set_lease<L: WorkerIdLease + Sync + 'static>(&self, lease: Arc<L>) {
+        self.inner.lock().set_lease(lease)
+    }
+
+    /// Set generator options
+    pub fn set_options(&self, options: IdGeneratorOptions) -> Result<(), OptionError> {
+        self.inner.lock().set_options(options)
+    }
+
+    /// Get generator options
+    pub fn get_options(&self) -> IdGeneratorOptions {
+        self.inner.lock().get_options()
+    }
+
+    /// Get a unique id
+    ///
+    /// While no id can be generated, i.e. the lease is lost or the tick store fails to save, it retries until
+    /// one can be. The generator is released between the retries, so a new lease can be set meanwhile.
+    /// Use `try_next_id` to get the error instead. The handles of the instances panic on a lost lease instead.
+    ///
+    /// # Panics
+    ///
+    /// With `IdWidth::Bits128`, see `CoreIdGenerator::next_id`.
+    pub fn next_id(&self) -> i64 {
+        retry(self.waits_for_lease(), || self.inner.lock().poll_id())
+    }
+
+    /// Get a unique id, or an error if no unique id can be generated
+    pub fn try_next_id(&self) -> Result<i64, GenerateError> {
+        self.inner.lock().try_next_id()
+    }
+
+    /// Get a unique id encoded in fixed width, see `CoreIdGenerator::next_id_string`
+    ///
+    /// Blocks like `next_id` while no id can be generated.
+    ///
+    /// # Panics
+    ///
+    /// With `IdWidth::Bits128`, see `CoreIdGenerator::next_id`.
+    pub fn next_id_string(&self, encoding: Encoding) -> String {
+        encoding.encode_fixed(self.next_u64())
+    }
+
+    /// Get a unique id of all the 64 bits, see `CoreIdGenerator::next_u64`
+    ///
+    /// Blocks like `next_id` while no id can be generated.
+    ///
+    /// # Panics
+    ///
+    /// With `IdWidth::Bits128`, see `CoreIdGenerator::next_id`.
+    pub fn next_u64(&self) -> u64 {
+        self.next_id() as u64
+    }
+
+    /// Get a unique id of all the 64 bits, or an error if no unique id can be generated
+    pub fn try_next_u64(&self) -> Result<u64, GenerateError> {
+        self.inner.lock().try_next_u64()
+    }
+
+    /// Get a unique 128-bit id, see `CoreIdGenerator::next_u128`
+    ///
+    /// Blocks like `next_id` while no id can be generated.
+    pub fn next_u128(&self) -> u128 {
+        retry(self.waits_for_lease(), || self.inner.lock().poll_u128())
+    }
+
+    /// Get a unique 128-bit id, or an error if no unique id can be generated
+    pub fn try_next_u128(&self) -> Result<u128, GenerateError> {
+        self.inner.lock().try_next_u128()
+    }
+
+    /// Get `n` unique ids at once
+    ///
+    /// Blocks like `next_id` while no id can be generated.
+    ///
+    /// # Panics
+    ///
+    /// With `IdWidth::Bits128`, see `CoreIdGenerator::next_id`.
+    pub fn next_ids(&self, n: usize) -> Vec<i64> {
+        let mut ids = vec![0; n];
+        self.fill_ids(&mut ids);
+        ids
+    }
+
+    /// Fill the slice with unique ids, see `CoreIdGenerator::fill_ids`
+    ///
+    /// Blocks like `next_id` while no id can be generated.
+    ///
+    /// # Panics
+    ///
+    /// With `IdWidth::Bits128`, see `CoreIdGenerator::next_id`.
+    pub fn fill_ids(&self, ids: &mut [i64]) {
+        let mut filled = 0;
+        while filled < ids.len() {
+            for id in retry(self.waits_for_lease(), || {
+                self.inner.lock().poll_id_range(ids.len() - filled)
+            }) {
+                ids[filled] = id;
+                filled += 1;
+            }
+        }
+    }
+
+    /// Reserve a block of `count` unique ids in advance, see `CoreIdGenerator::lease_block`
+    ///
+    /// Blocks like `next_id` while no id can be generated.
+    ///
+    /// # Panics
+    ///
+    /// With `IdWidth::Bits128`, see `CoreIdGenerator::next_id`.
+    pub fn lease_block(&self, count: usize) -> IdBlock {
+        let mut block = IdBlock::default();
+        while block.remaining() < count {
+            block.push(retry(self.waits_for_lease(), || {
+                self.inner.lock().poll_id_range(count - block.remaining())
+            }));
+        }
+        block
+    }
+
+    /// How long and how fast ids can be generated with the current options
+    pub fn horizon(&self) -> Horizon {
+        self.inner.lock().horizon()
+    }
+
+    /// Decode an id generated with the current options
+    pub fn decode(&self, id: i64) -> DecodedId {
+        self.inner.lock().decode(id)
+    }
+
+    /// Panic with `GenerateError::LeaseLost` instead of waiting for a new lease, for the instances
+    pub(crate) fn panicking_on_lease_loss(mut self) -> Self {
+        self.panic_on_lease_loss = true;
+        self
+    }
+
+    fn waits_for_lease(&self) -> bool {
+        !self.panic_on_lease_loss
+    }
+}
+
+impl From<CoreIdGenerator> for IdGenerator {
+    fn from(generator: CoreIdGenerator) -> Self {
+        IdGenerator {
+            inner: Arc::new(Mutex::new(generator)),
+            panic_on_lease_loss: false,
+        }
+    }
+}
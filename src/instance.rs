@@ -6,40 +6,113 @@
 //! - `IdVecInstance`: a instance with multiple generators. See [examples/multiple.rs](https://github.com/BobAnkh/idgenerator/blob/main/examples/multiple.rs) for usage example.
 
 use once_cell::sync::OnceCell;
-use parking_lot::{Mutex, RwLock};
-use std::sync::Arc;
+use parking_lot::RwLock;
+use std::time::Instant;
 
 use crate::CoreIdGenerator;
+use crate::Encoding;
+use crate::IdBlock;
+use crate::IdGenerator;
 use crate::IdGeneratorOptions;
-use crate::OptionError;
+use crate::{GenerateError, OptionError};
 
 /// Instance of only one generator
+///
+/// A process-wide `IdGenerator`, see `IdGenerator` for a generator owned by yourself.
 pub struct IdInstance;
 
 impl IdInstance {
     /// Initialize the instance
     pub fn init(options: IdGeneratorOptions) -> Result<(), OptionError> {
-        IdInstance::get_instance().lock().init(options)
+        IdInstance::get_instance().init(options)
     }
 
     /// Set instance options
     pub fn set_options(options: IdGeneratorOptions) -> Result<(), OptionError> {
-        IdInstance::get_instance().lock().set_options(options)
+        IdInstance::get_instance().set_options(options)
     }
 
     /// Get instance options
     pub fn get_options() -> IdGeneratorOptions {
-        IdInstance::get_instance().lock().get_options()
+        IdInstance::get_instance().get_options()
     }
 
     /// Get a unique id
+    ///
+    /// # Panics
+    ///
+    /// Once the lease is lost, or with `IdWidth::Bits128`, see `CoreIdGenerator::next_id`.
     pub fn next_id() -> i64 {
-        IdInstance::get_instance().lock().next_id()
+        IdInstance::get_instance().next_id()
+    }
+
+    /// Get a unique id, or an error if no unique id can be generated
+    pub fn try_next_id() -> Result<i64, GenerateError> {
+        IdInstance::get_instance().try_next_id()
+    }
+
+    /// Get a unique id encoded in fixed width, see `CoreIdGenerator::next_id_string`
+    ///
+    /// # Panics
+    ///
+    /// Once the lease is lost, or with `IdWidth::Bits128`, see `CoreIdGenerator::next_id`.
+    pub fn next_id_string(encoding: Encoding) -> String {
+        IdInstance::get_instance().next_id_string(encoding)
+    }
+
+    /// Get a unique id of all the 64 bits, see `CoreIdGenerator::next_u64`
+    ///
+    /// # Panics
+    ///
+    /// Once the lease is lost, or with `IdWidth::Bits128`, see `CoreIdGenerator::next_id`.
+    pub fn next_u64() -> u64 {
+        IdInstance::get_instance().next_u64()
+    }
+
+    /// Get a unique 128-bit id, see `CoreIdGenerator::next_u128`
+    ///
+    /// # Panics
+    ///
+    /// Once the lease is lost, see `CoreIdGenerator::next_id`.
+    pub fn next_u128() -> u128 {
+        IdInstance::get_instance().next_u128()
+    }
+
+    /// Get `n` unique ids at once
+    ///
+    /// # Panics
+    ///
+    /// Once the lease is lost, or with `IdWidth::Bits128`, see `CoreIdGenerator::next_id`.
+    pub fn next_ids(n: usize) -> Vec<i64> {
+        IdInstance::get_instance().next_ids(n)
+    }
+
+    /// Fill the slice with unique ids
+    ///
+    /// # Panics
+    ///
+    /// Once the lease is lost, or with `IdWidth::Bits128`, see `CoreIdGenerator::next_id`.
+    pub fn fill_ids(ids: &mut [i64]) {
+        IdInstance::get_instance().fill_ids(ids)
+    }
+
+    /// Reserve a block of `count` unique ids in advance
+    ///
+    /// # Panics
+    ///
+    /// Once the lease is lost, or with `IdWidth::Bits128`, see `CoreIdGenerator::next_id`.
+    pub fn lease_block(count: usize) -> IdBlock {
+        IdInstance::get_instance().lease_block(count)
+    }
+
+    /// Get a handle of the generator inside the instance, which panics like the instance once the lease is lost
+    pub fn generator() -> IdGenerator {
+        IdInstance::get_instance().clone()
     }
 
-    fn get_instance() -> &'static Mutex<CoreIdGenerator> {
-        static INSTANCE: OnceCell<Mutex<CoreIdGenerator>> = OnceCell::new();
-        INSTANCE.get_or_init(|| Mutex::new(CoreIdGenerator::default()))
+    fn get_instance() -> &'static IdGenerator {
+        static INSTANCE: OnceCell<IdGenerator> = OnceCell::new();
+        INSTANCE.get_or_init(|| IdGenerator::default().panicking_on_lease_loss())
     }
 }
 
@@ -50,60 +123,138 @@ impl IdVecInstance {
     /// Initialize the instance
     ///
     /// Every time you call this function will drop all the previous generators in the instance.
-    pub fn init(mut options: Vec<IdGeneratorOptions>) -> Result<(), OptionError> {
+    /// The startup strategies are waited for once for all the generators, after creating them.
+    pub fn init(options: Vec<IdGeneratorOptions>) -> Result<(), OptionError> {
         if options.is_empty() {
             return Err(OptionError::InvalidVecLen(0));
         }
-        let mut instances = IdVecInstance::get_instance().write();
-        instances.clear();
-        for option in options.drain(..) {
-            let mut instance = CoreIdGenerator::default();
-            instance.init(option)?;
-            instances.push(Arc::new(Mutex::new(instance)));
+        let since = Instant::now();
+        let mut generators = Vec::with_capacity(options.len());
+        for option in options {
+            let mut generator = CoreIdGenerator::default();
+            generator.init_deferred(option)?;
+            generators.push(generator);
+        }
+        for generator in &generators {
+            generator.wait_for_startup(since);
         }
+        let mut instances = IdVecInstance::get_instance().write();
+        *instances = generators
+            .into_iter()
+            .map(|generator| IdGenerator::from(generator).panicking_on_lease_loss())
+            .collect();
         Ok(())
     }
 
     /// Set instance options of the given index
     pub fn set_options(index: usize, options: IdGeneratorOptions) -> Result<(), OptionError> {
-        let reader = {
-            let r = IdVecInstance::get_instance().read();
-            if index >= r.len() {
-                return Err(OptionError::IndexOutOfRange(index));
-            }
-            Arc::clone(&r[index])
-        };
-        reader.lock().set_options(options)?;
-        Ok(())
+        IdVecInstance::generator(index)
+            .ok_or(OptionError::IndexOutOfRange(index))?
+            .set_options(options)
     }
 
     /// Get instance options of the given index
     pub fn get_options(index: usize) -> Result<IdGeneratorOptions, OptionError> {
-        let reader = {
-            let r = IdVecInstance::get_instance().read();
-            if index >= r.len() {
-                return Err(OptionError::IndexOutOfRange(index));
-            }
-            Arc::clone(&r[index])
-        };
-        let options = reader.lock().get_options();
-        Ok(options)
+        let generator =
+            IdVecInstance::generator(index).ok_or(OptionError::IndexOutOfRange(index))?;
+        Ok(generator.get_options())
     }
 
     /// Get a unique id
+    ///
+    /// # Panics
+    ///
+    /// If the index is invalid, once the lease is lost or with `IdWidth::Bits128`, see `CoreIdGenerator::next_id`.
     pub fn next_id(index: usize) -> i64 {
         // Because this step matters the speed a lot,
         // so we won't check the index and let it panic
-        let reader = {
+        let generator = IdVecInstance::get_instance().read()[index].clone();
+        generator.next_id()
+    }
+
+    /// Get a unique id, or an error if the index is invalid or no unique id can be generated
+    pub fn try_next_id(index: usize) -> Result<i64, GenerateError> {
+        let generator = {
             let r = IdVecInstance::get_instance().read();
-            Arc::clone(&r[index])
+            if r.is_empty() {
+                return Err(GenerateError::Uninitialized);
+            }
+            r.get(index)
+                .cloned()
+                .ok_or(GenerateError::IndexOutOfRange(index))?
         };
-        let id = reader.lock().next_id();
-        id
+        generator.try_next_id()
+    }
+
+    /// Get a unique id encoded in fixed width from the generator of the given index, see `CoreIdGenerator::next_id_string`
+    ///
+    /// # Panics
+    ///
+    /// If the index is invalid, once the lease is lost or with `IdWidth::Bits128`, see `CoreIdGenerator::next_id`.
+    pub fn next_id_string(index: usize, encoding: Encoding) -> String {
+        let generator = IdVecInstance::get_instance().read()[index].clone();
+        generator.next_id_string(encoding)
+    }
+
+    /// Get a unique id of all the 64 bits from the generator of the given index, see `CoreIdGenerator::next_u64`
+    ///
+    /// # Panics
+    ///
+    /// If the index is invalid, once the lease is lost or with `IdWidth::Bits128`, see `CoreIdGenerator::next_id`.
+    pub fn next_u64(index: usize) -> u64 {
+        let generator = IdVecInstance::get_instance().read()[index].clone();
+        generator.next_u64()
+    }
+
+    /// Get a unique 128-bit id from the generator of the given index, see `CoreIdGenerator::next_u128`
+    ///
+    /// # Panics
+    ///
+    /// If the index is invalid, or once the lease is lost, see `CoreIdGenerator::next_id`.
+    pub fn next_u128(index: usize) -> u128 {
+        let generator = IdVecInstance::get_instance().read()[index].clone();
+        generator.next_u128()
+    }
+
+    /// Get `n` unique ids at once from the generator of the given index
+    ///
+    /// # Panics
+    ///
+    /// If the index is invalid, once the lease is lost or with `IdWidth::Bits128`, see `CoreIdGenerator::next_id`.
+    pub fn next_ids(index: usize, n: usize) -> Vec<i64> {
+        let generator = IdVecInstance::get_instance().read()[index].clone();
+        generator.next_ids(n)
+    }
+
+    /// Fill the slice with unique ids from the generator of the given index
+    ///
+    /// # Panics
+    ///
+    /// If the index is invalid, once the lease is lost or with `IdWidth::Bits128`, see `CoreIdGenerator::next_id`.
+    pub fn fill_ids(index: usize, ids: &mut [i64]) {
+        let generator = IdVecInstance::get_instance().read()[index].clone();
+        generator.fill_ids(ids);
+    }
+
+    /// Reserve a block of `count` unique ids in advance from the generator of the given index
+    ///
+    /// # Panics
+    ///
+    /// If the index is invalid, once the lease is lost or with `IdWidth::Bits128`, see `CoreIdGenerator::next_id`.
+    pub fn lease_block(index: usize, count: usize) -> IdBlock {
+        let generator = IdVecInstance::get_instance().read()[index].clone();
+        generator.lease_block(count)
+    }
+
+    /// Get a handle of the generator of the given index, `None` if the index is invalid
+    ///
+    /// The handle panics like the instance once the lease is lost.
+    pub fn generator(index: usize) -> Option<IdGenerator> {
+        IdVecInstance::get_instance().read().get(index).cloned()
     }
 
-    fn get_instance() -> &'static RwLock<Vec<Arc<Mutex<CoreIdGenerator>>>> {
-        static INSTANCE: OnceCell<RwLock<Vec<Arc<Mutex<CoreIdGenerator>>>>> = OnceCell::new();
+    fn get_instance() -> &'static RwLock<Vec<IdGenerator>> {
+        static INSTANCE: OnceCell<RwLock<Vec<IdGenerator>>> = OnceCell::new();
         INSTANCE.get_or_init(|| RwLock::new(Vec::new()))
     }
 }
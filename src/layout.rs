@@ -0,0 +1,233 @@
+//! # Layout
+//!
+//! Custom bit layout of the ids.
+//!
+//! By default an id is composed of `time_tick | datacenter_id | worker_id | sequence` from the most significant bits,
+//! with the bit lengths given by the options. `IdLayout` describes any other order and bit lengths instead,
+//! with constant fields like a shard or type, e.g. to match the ids of other systems:
+//!
+//! - `IdLayout::sonyflake()`: `time_tick (39) | sequence (8) | worker_id (16)`.
+//! - `IdLayout::instagram(shard_id)`: `time_tick (40) | shard (13) | sequence (10)`.
+//!
+//! The sign bit is reserved, so the fields take at most 63 bits, or 64 bits with `IdWidth::Bits64`.
+
+use crate::OptionError;
+
+/// A field of the id layout
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LayoutField {
+    /// Time tick since `base_time`, of the bit length
+    Timestamp(u8),
+    /// Sequence number within a time tick, of the bit length
+    Sequence(u8),
+    /// `worker_id`, of the bit length
+    Worker(u8),
+    /// `datacenter_id`, of the bit length
+    Datacenter(u8),
+    /// Named field with a constant value, e.g. a shard or type
+    Custom {
+        name: String,
+        bit_len: u8,
+        value: u64,
+    },
+}
+
+impl LayoutField {
+    pub fn bit_len(&self) -> u8 {
+        match self {
+            LayoutField::Timestamp(bit_len)
+            | LayoutField::Sequence(bit_len)
+            | LayoutField::Worker(bit_len)
+            | LayoutField::Datacenter(bit_len) => *bit_len,
+            LayoutField::Custom { bit_len, .. } => *bit_len,
+        }
+    }
+}
+
+/// Ordered fields of the id from the most significant one, packed into the low bits
+///
+/// A layout has exactly one timestamp and one sequence field, and at most one worker and one datacenter field.
+/// Their bit lengths take precedence over `worker_id_bit_len`, `datacenter_id_bit_len` and `seq_bit_len` of the options,
+/// and the fields not in the layout are 0. The bits above the fields, including the sign bit, are left 0.
+/// Layouts only apply to 64-bit ids, i.e. not to `IdWidth::Bits128`.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct IdLayout {
+    fields: Vec<LayoutField>,
+}
+
+impl IdLayout {
+    pub fn new() -> Self {
+        IdLayout::default()
+    }
+
+    /// Sonyflake: 39 bits of time tick, 8 bits of sequence and 16 bits of worker id
+    ///
+    /// Use it along with `TimeUnit::TenMilliseconds` to be compatible with Sonyflake.
+    pub fn sonyflake() -> Self {
+        IdLayout::new().timestamp(39).sequence(8).worker(16)
+    }
+
+    /// Instagram: 41 bits of time tick, 13 bits of shard id and 10 bits of sequence
+    ///
+    /// The highest bit of the Instagram time tick is the sign bit here, so the time tick takes 40 bits,
+    /// which is enough for the first 34 years after `base_time`.
+    pub fn instagram(shard_id: u64) -> Self {
+        IdLayout::new()
+            .timestamp(40)
+            .custom("shard", 13, shard_id)
+            .sequence(10)
+    }
+
+    /// Append a time tick field of the bit length
+    pub fn timestamp(self, bit_len: u8) -> Self {
+        self.field(LayoutField::Timestamp(bit_len))
+    }
+
+    /// Append a sequence field of the bit length
+    pub fn sequence(self, bit_len: u8) -> Self {
+        self.field(LayoutField::Sequence(bit_len))
+    }
+
+    /// Append a worker id field of the bit length
+    pub fn worker(self, bit_len: u8) -> Self {
+        self.field(LayoutField::Worker(bit_len))
+    }
+
+    /// Append a datacenter id field of the bit length
+    pub fn datacenter(self, bit_len: u8) -> Self {
+        self.field(LayoutField::Datacenter(bit_len))
+    }
+
+    /// Append a named field of the bit length holding a constant value
+    pub fn custom<S: Into<String>>(self, name: S, bit_len: u8, value: u64) -> Self {
+        self.field(LayoutField::Custom {
+            name: name.into(),
+            bit_len,
+            value,
+        })
+    }
+
+    /// Append a field
+    pub fn field(mut self, field: LayoutField) -> Self {
+        self.fields.push(field);
+        self
+    }
+
+    pub fn fields(&self) -> &[LayoutField] {
+        &self.fields
+    }
+
+    /// Total bit length of the fields
+    pub fn bit_len(&self) -> u32 {
+        self.fields.iter().map(|field| field.bit_len() as u32).sum()
+    }
+
+    /// The default layout `time_tick | datacenter_id | worker_id | sequence` taking all the `bit_len` bits
+    pub(crate) fn standard(
+        bit_len: u8,
+        datacenter_id_bit_len: u8,
+        worker_id_bit_len: u8,
+        seq_bit_len: u8,
+    ) -> Self {
+        let mut layout = IdLayout::new().timestamp(
+            bit_len
+                .saturating_sub(datacenter_id_bit_len)
+                .saturating_sub(worker_id_bit_len)
+                .saturating_sub(seq_bit_len),
+        );
+        if datacenter_id_bit_len > 0 {
+            layout = layout.datacenter(datacenter_id_bit_len);
+        }
+        layout.worker(worker_id_bit_len).sequence(seq_bit_len)
+    }
+
+    /// Check the fields of the layout against the bit width of the ids
+    pub(crate) fn check(&self, max_bit_len: u8) -> Result<(), OptionError> {
+        let count =
+            |matches: fn(&LayoutField) -> bool| self.fields.iter().filter(|f| matches(f)).count();
+        if count(|field| matches!(field, LayoutField::Timestamp(_))) != 1 {
+            return Err(OptionError::InvalidLayout(
+                "should have exactly one timestamp field".to_string(),
+            ));
+        }
+        if count(|field| matches!(field, LayoutField::Sequence(_))) != 1 {
+            return Err(OptionError::InvalidLayout(
+                "should have exactly one sequence field".to_string(),
+            ));
+        }
+        if count(|field| matches!(field, LayoutField::Worker(_))) > 1
+            || count(|field| matches!(field, LayoutField::Datacenter(_))) > 1
+        {
+            return Err(OptionError::InvalidLayout(
+                "should have at most one worker field and one datacenter field".to_string(),
+            ));
+        }
+        for field in &self.fields {
+            if field.bit_len() == 0 {
+                return Err(OptionError::InvalidLayout(format!(
+                    "should have a positive bit length for {field:?}"
+                )));
+            }
+            if let LayoutField::Worker(bit_len) | LayoutField::Datacenter(bit_len) = field {
+                if *bit_len > 31 {
+                    return Err(OptionError::InvalidLayout(format!(
+                        "should have at most 31 bits for {field:?}"
+                    )));
+                }
+            }
+            if let LayoutField::Custom { bit_len, value, .. } = field {
+                if *bit_len < 64 && *value >> bit_len != 0 {
+                    return Err(OptionError::InvalidLayout(format!(
+                        "should have the value of {field:?} fit in its bit length"
+                    )));
+                }
+            }
+        }
+        if self.bit_len() > max_bit_len as u32 {
+            return Err(OptionError::InvalidLayout(format!(
+                "should have at most {max_bit_len} bits in total, but {} bits",
+                self.bit_len()
+            )));
+        }
+        Ok(())
+    }
+
+    /// Shift and bit length of the first field matching, (0, 0) if none
+    pub(crate) fn position(&self, matches: fn(&LayoutField) -> bool) -> (u8, u8) {
+        let mut shift = 0;
+        for field in self.fields.iter().rev() {
+            if matches(field) {
+                return (shift, field.bit_len());
+            }
+            shift += field.bit_len();
+        }
+        (0, 0)
+    }
+
+    /// The custom fields composed at their positions
+    pub(crate) fn custom_part(&self) -> i64 {
+        let mut part = 0;
+        let mut shift = 0;
+        for field in self.fields.iter().rev() {
+            if let LayoutField::Custom { value, .. } = field {
+                part += (*value as i64) << shift;
+            }
+            shift += field.bit_len();
+        }
+        part
+    }
+
+    /// Values of the custom fields in the id
+    pub(crate) fn custom_values(&self, id: i64) -> Vec<(String, u64)> {
+        let mut values = Vec::new();
+        let mut shift = 0;
+        for field in self.fields.iter().rev() {
+            if let LayoutField::Custom { name, bit_len, .. } = field {
+                values.push((name.clone(), ((id >> shift) as u64) & ((1 << bit_len) - 1)));
+            }
+            shift += field.bit_len();
+        }
+        values.reverse();
+        values
+    }
+}
@@ -9,25 +9,91 @@
 //! - `IdInstance`: a instance with only one generator. See [examples/single.rs](https://github.com/BobAnkh/idgenerator/blob/main/examples/single.rs) for usage example.
 //! - `IdVecInstance`: a instance with multiple generators. See [examples/multiple.rs](https://github.com/BobAnkh/idgenerator/blob/main/examples/multiple.rs) for usage example.
 //!
+//! Both of them are process-wide. Use `IdGenerator` for a cloneable generator handle owned by yourself,
+//! which can be passed around with dependency injection.
+//!
+//! `IdRegistry` holds multiple generators addressed by name (or any other key type) instead of index.
+//!
+//! ## Lock-free generator
+//!
+//! `AtomicIdGenerator` generates ids with the same layout as `CoreIdGenerator` without any lock,
+//! which suits generating ids from many threads at the same time. Its drift is bounded in total
+//! and it has no turn back handling, see the `atomic` module.
+//!
 //! ## Self-implement instance
 //!
 //! This lib wraps the snowflake algorithm inside the `CoreIdGenerator` struct.
 //! You can wrap this struct inside your own instance of unique id generator.
 //!
+//! The generator reads the current time from a `Clock`, which is the system clock by default.
+//! Use `CoreIdGenerator::with_clock` to provide another one, e.g. `MonotonicClock` or `ManualClock`.
+//!
+//! To survive restarts with the clock set backwards, use `CoreIdGenerator::set_tick_store` to persist
+//! the time ids are generated up to in a `TickStore`, e.g. `FileTickStore`.
+//!
 //! ## Others
 //!
 //! - `IdGeneratorOptions`: options for id generator.
+//! - `Horizon`: how long and how fast ids can be generated with the options.
+//! - `IdWidth`: positive `i64` ids by default, all the 64 bits with `next_u64`, or 128-bit ids with `next_u128`.
+//! - `IdLayout`: custom order and bit lengths of the fields of the ids, e.g. to match Sonyflake or Instagram ids.
 //! - `OptionError`: errors for setting options.
+//! - `GenerateError`: errors for generating ids with `try_next_id`.
+//! - `WorkerIdAllocator`: allocate worker ids for generators, e.g. `FileLockAllocator` for multiple processes on the same host,
+//!   or `SqlLeaseAllocator` (feature `sql`) and `RedisLeaseAllocator` (feature `redis`) for a fleet of hosts.
+//! - `WorkerIdSource`: derive worker ids from deployment facts, e.g. the ordinal of a StatefulSet pod.
+//! - `IdBlock`: a block of unique ids reserved in advance with `lease_block`, to be handed out without touching the generator.
+//! - `decode`: split an id back into its timestamp, worker id and sequence.
+//! - `Encoding`: compact strings of the ids, i.e. base62, Crockford base32 and base36, sorting in the order of the ids in fixed width.
+//! - `TypedId`: ids of a kind defined with `define_id!`, displayed with a prefix like `usr_0AbCdEfGhIj`, which can not be mixed up with the ids of other kinds.
+//! - `Checksum`: check characters appended to the encoded ids, detecting typos of ids typed by hand.
+//! - `Sqids`: short strings of one or more ids, compatible with the Sqids libraries of other languages.
+//! - `IdObfuscator`: opaque external ids by a keyed permutation of the ids, reversible with key rotation.
 
+pub mod allocator;
+pub mod atomic;
+pub mod block;
+pub mod checksum;
+pub mod clock;
+pub mod decoder;
+pub mod encoding;
 pub mod error;
 pub mod generator;
+pub mod handle;
 pub mod instance;
+pub mod layout;
+pub mod obfuscator;
 pub mod options;
+pub mod registry;
+pub mod sqids;
+pub mod store;
+pub mod typed;
 
-pub use error::OptionError;
+#[cfg(feature = "redis")]
+pub use allocator::RedisLeaseAllocator;
+#[cfg(feature = "sql")]
+pub use allocator::SqlLeaseAllocator;
+pub use allocator::{FileLockAllocator, WorkerIdAllocator, WorkerIdLease, WorkerIdSource};
+pub use atomic::AtomicIdGenerator;
+pub use block::IdBlock;
+pub use checksum::Checksum;
+pub use clock::{Clock, ManualClock, MonotonicClock, SystemClock};
+pub use decoder::{decode, decode_u128, DecodedId};
+pub use encoding::{normalize_crockford32, Encoding};
+pub use error::{
+    AllocateError, ChecksumError, EncodingError, GenerateError, ObfuscationError, OptionError,
+    ParseIdError, SqidsError,
+};
 pub use generator::CoreIdGenerator;
+pub use handle::IdGenerator;
 pub use instance::{IdInstance, IdVecInstance};
-pub use options::IdGeneratorOptions;
+pub use layout::{IdLayout, LayoutField};
+pub use obfuscator::IdObfuscator;
+pub use options::{Horizon, IdGeneratorOptions, IdWidth, StartupStrategy, TimeUnit};
+pub use registry::IdRegistry;
+pub use sqids::{Sqids, SqidsOptions};
+pub use store::{FileTickStore, TickStore};
+pub use typed::{IdKind, TypedId};
 
 #[cfg(test)]
 mod tests {
@@ -48,12 +114,19 @@ mod tests {
             IdGeneratorOptions {
                 method: Some(1),
                 base_time: Some(1582136402000),
+                time_unit: Some(TimeUnit::Millisecond),
                 worker_id: Some(1),
                 worker_id_bit_len: Some(6),
+                datacenter_id: Some(0),
+                datacenter_id_bit_len: Some(0),
                 seq_bit_len: Some(8),
                 max_seq_num: Some(255),
                 min_seq_num: Some(5),
                 top_over_cost_count: Some(2000),
+                min_horizon_years: Some(0),
+                startup_strategy: Some(StartupStrategy::Sleep),
+                id_width: Some(IdWidth::Bits63),
+                layout: None,
             }
         );
         let options = IdGeneratorOptions::new().seq_bit_len(12);
@@ -65,12 +138,19 @@ mod tests {
             IdGeneratorOptions {
                 method: Some(1),
                 base_time: Some(1582136402000),
+                time_unit: Some(TimeUnit::Millisecond),
                 worker_id: Some(1),
                 worker_id_bit_len: Some(6),
+                datacenter_id: Some(0),
+                datacenter_id_bit_len: Some(0),
                 seq_bit_len: Some(12),
                 max_seq_num: Some(255),
                 min_seq_num: Some(5),
                 top_over_cost_count: Some(2000),
+                min_horizon_years: Some(0),
+                startup_strategy: Some(StartupStrategy::Sleep),
+                id_width: Some(IdWidth::Bits63),
+                layout: None,
             }
         );
         let start = Instant::now();
@@ -100,12 +180,19 @@ mod tests {
             IdGeneratorOptions {
                 method: Some(1),
                 base_time: Some(1582136402000),
+                time_unit: Some(TimeUnit::Millisecond),
                 worker_id: Some(1),
                 worker_id_bit_len: Some(6),
+                datacenter_id: Some(0),
+                datacenter_id_bit_len: Some(0),
                 seq_bit_len: Some(8),
                 max_seq_num: Some(255),
                 min_seq_num: Some(5),
                 top_over_cost_count: Some(2000),
+                min_horizon_years: Some(0),
+                startup_strategy: Some(StartupStrategy::Sleep),
+                id_width: Some(IdWidth::Bits63),
+                layout: None,
             }
         );
         let options = IdGeneratorOptions::new().seq_bit_len(12);
@@ -117,12 +204,19 @@ mod tests {
             IdGeneratorOptions {
                 method: Some(1),
                 base_time: Some(1582136402000),
+                time_unit: Some(TimeUnit::Millisecond),
                 worker_id: Some(1),
                 worker_id_bit_len: Some(6),
+                datacenter_id: Some(0),
+                datacenter_id_bit_len: Some(0),
                 seq_bit_len: Some(12),
                 max_seq_num: Some(255),
                 min_seq_num: Some(5),
                 top_over_cost_count: Some(2000),
+                min_horizon_years: Some(0),
+                startup_strategy: Some(StartupStrategy::Sleep),
+                id_width: Some(IdWidth::Bits63),
+                layout: None,
             }
         );
         let start = Instant::now();
@@ -157,12 +251,19 @@ mod tests {
             IdGeneratorOptions {
                 method: Some(1),
                 base_time: Some(1582136402000),
+                time_unit: Some(TimeUnit::Millisecond),
                 worker_id: Some(1),
                 worker_id_bit_len: Some(6),
+                datacenter_id: Some(0),
+                datacenter_id_bit_len: Some(0),
                 seq_bit_len: Some(8),
                 max_seq_num: Some(255),
                 min_seq_num: Some(5),
                 top_over_cost_count: Some(2000),
+                min_horizon_years: Some(0),
+                startup_strategy: Some(StartupStrategy::Sleep),
+                id_width: Some(IdWidth::Bits63),
+                layout: None,
             }
         );
         let options = IdVecInstance::get_options(1).unwrap();
@@ -171,12 +272,19 @@ mod tests {
             IdGeneratorOptions {
                 method: Some(1),
                 base_time: Some(1582136402000),
+                time_unit: Some(TimeUnit::Millisecond),
                 worker_id: Some(2),
                 worker_id_bit_len: Some(6),
+                datacenter_id: Some(0),
+                datacenter_id_bit_len: Some(0),
                 seq_bit_len: Some(8),
                 max_seq_num: Some(255),
                 min_seq_num: Some(5),
                 top_over_cost_count: Some(2000),
+                min_horizon_years: Some(0),
+                startup_strategy: Some(StartupStrategy::Sleep),
+                id_width: Some(IdWidth::Bits63),
+                layout: None,
             }
         );
         let options = IdGeneratorOptions::new().seq_bit_len(12);
@@ -190,12 +298,19 @@ mod tests {
             IdGeneratorOptions {
                 method: Some(1),
                 base_time: Some(1582136402000),
+                time_unit: Some(TimeUnit::Millisecond),
                 worker_id: Some(1),
                 worker_id_bit_len: Some(6),
+                datacenter_id: Some(0),
+                datacenter_id_bit_len: Some(0),
                 seq_bit_len: Some(12),
                 max_seq_num: Some(255),
                 min_seq_num: Some(5),
                 top_over_cost_count: Some(2000),
+                min_horizon_years: Some(0),
+                startup_strategy: Some(StartupStrategy::Sleep),
+                id_width: Some(IdWidth::Bits63),
+                layout: None,
             }
         );
         let options = IdVecInstance::get_options(1).unwrap();
@@ -204,12 +319,19 @@ mod tests {
             IdGeneratorOptions {
                 method: Some(1),
                 base_time: Some(1582136402000),
+                time_unit: Some(TimeUnit::Millisecond),
                 worker_id: Some(2),
                 worker_id_bit_len: Some(6),
+                datacenter_id: Some(0),
+                datacenter_id_bit_len: Some(0),
                 seq_bit_len: Some(12),
                 max_seq_num: Some(255),
                 min_seq_num: Some(5),
                 top_over_cost_count: Some(2000),
+                min_horizon_years: Some(0),
+                startup_strategy: Some(StartupStrategy::Sleep),
+                id_width: Some(IdWidth::Bits63),
+                layout: None,
             }
         );
         let start = Instant::now();
@@ -244,12 +366,19 @@ mod tests {
             IdGeneratorOptions {
                 method: Some(1),
                 base_time: Some(1582136402000),
+                time_unit: Some(TimeUnit::Millisecond),
                 worker_id: Some(1),
                 worker_id_bit_len: Some(6),
+                datacenter_id: Some(0),
+                datacenter_id_bit_len: Some(0),
                 seq_bit_len: Some(8),
                 max_seq_num: Some(255),
                 min_seq_num: Some(5),
                 top_over_cost_count: Some(2000),
+                min_horizon_years: Some(0),
+                startup_strategy: Some(StartupStrategy::Sleep),
+                id_width: Some(IdWidth::Bits63),
+                layout: None,
             }
         );
         let options = IdVecInstance::get_options(1).unwrap();
@@ -258,12 +387,19 @@ mod tests {
             IdGeneratorOptions {
                 method: Some(1),
                 base_time: Some(1582136402000),
+                time_unit: Some(TimeUnit::Millisecond),
                 worker_id: Some(2),
                 worker_id_bit_len: Some(6),
+                datacenter_id: Some(0),
+                datacenter_id_bit_len: Some(0),
                 seq_bit_len: Some(8),
                 max_seq_num: Some(255),
                 min_seq_num: Some(5),
                 top_over_cost_count: Some(2000),
+                min_horizon_years: Some(0),
+                startup_strategy: Some(StartupStrategy::Sleep),
+                id_width: Some(IdWidth::Bits63),
+                layout: None,
             }
         );
         let options = IdGeneratorOptions::new().seq_bit_len(12);
@@ -277,12 +413,19 @@ mod tests {
             IdGeneratorOptions {
                 method: Some(1),
                 base_time: Some(1582136402000),
+                time_unit: Some(TimeUnit::Millisecond),
                 worker_id: Some(1),
                 worker_id_bit_len: Some(6),
+                datacenter_id: Some(0),
+                datacenter_id_bit_len: Some(0),
                 seq_bit_len: Some(12),
                 max_seq_num: Some(255),
                 min_seq_num: Some(5),
                 top_over_cost_count: Some(2000),
+                min_horizon_years: Some(0),
+                startup_strategy: Some(StartupStrategy::Sleep),
+                id_width: Some(IdWidth::Bits63),
+                layout: None,
             }
         );
         let options = IdVecInstance::get_options(1).unwrap();
@@ -291,12 +434,19 @@ mod tests {
             IdGeneratorOptions {
                 method: Some(1),
                 base_time: Some(1582136402000),
+                time_unit: Some(TimeUnit::Millisecond),
                 worker_id: Some(2),
                 worker_id_bit_len: Some(6),
+                datacenter_id: Some(0),
+                datacenter_id_bit_len: Some(0),
                 seq_bit_len: Some(12),
                 max_seq_num: Some(255),
                 min_seq_num: Some(5),
                 top_over_cost_count: Some(2000),
+                min_horizon_years: Some(0),
+                startup_strategy: Some(StartupStrategy::Sleep),
+                id_width: Some(IdWidth::Bits63),
+                layout: None,
             }
         );
         let start = Instant::now();
@@ -336,15 +486,28 @@ mod tests {
             IdGeneratorOptions {
                 method: Some(1),
                 base_time: Some(1582136402000),
+                time_unit: Some(TimeUnit::Millisecond),
                 worker_id: Some(1),
                 worker_id_bit_len: Some(6),
+                datacenter_id: Some(0),
+                datacenter_id_bit_len: Some(0),
                 seq_bit_len: Some(8),
                 max_seq_num: Some(255),
                 min_seq_num: Some(5),
                 top_over_cost_count: Some(2000),
+                min_horizon_years: Some(0),
+                startup_strategy: Some(StartupStrategy::Sleep),
+                id_width: Some(IdWidth::Bits63),
+                layout: None,
             }
         );
 
+        let options = IdGeneratorOptions::new().method(3);
+        assert_eq!(
+            IdInstance::set_options(options),
+            Err(OptionError::InvalidMethod)
+        );
+
         let options = IdGeneratorOptions::new().base_time(12);
         assert_eq!(
             IdInstance::set_options(options),
@@ -405,20 +568,1551 @@ mod tests {
         let options: Vec<IdGeneratorOptions> = Vec::new();
         let res = IdVecInstance::init(options);
         assert_eq!(res, Err(OptionError::InvalidVecLen(0)));
-        let options = vec![
-            IdGeneratorOptions::new().worker_id(1).worker_id_bit_len(6),
-            IdGeneratorOptions::new().worker_id(2).worker_id_bit_len(6),
-        ];
+        // The startup sleeps of the generators are waited for once
+        let options = (1..=8)
+            .map(|worker_id| {
+                IdGeneratorOptions::new()
+                    .worker_id(worker_id)
+                    .worker_id_bit_len(6)
+            })
+            .collect();
+        let start = Instant::now();
         let res = IdVecInstance::init(options);
         assert!(res.is_ok());
+        let elapsed = start.elapsed().as_millis();
+        assert!((500..1000).contains(&elapsed));
         assert_eq!(
-            IdVecInstance::get_options(3),
-            Err(OptionError::IndexOutOfRange(3))
+            IdVecInstance::get_options(8),
+            Err(OptionError::IndexOutOfRange(8))
         );
         let option = IdGeneratorOptions::new().seq_bit_len(12);
         assert_eq!(
-            IdVecInstance::set_options(3, option),
-            Err(OptionError::IndexOutOfRange(3))
+            IdVecInstance::set_options(8, option),
+            Err(OptionError::IndexOutOfRange(8))
+        );
+    }
+
+    #[test]
+    fn test_decode() {
+        let options = IdGeneratorOptions::new().worker_id(3).worker_id_bit_len(6);
+        let id = (1000 << 14) + (3 << 8) + 7;
+        let decoded = decode(id, &options);
+        assert_eq!(
+            decoded,
+            DecodedId {
+                time_tick: 1000,
+                timestamp: 1582136403000,
+                datacenter_id: 0,
+                worker_id: 3,
+                seq: 7,
+                custom: vec![],
+                is_turn_back: false,
+                is_manual: false,
+            }
+        );
+        assert!(decode((1000 << 14) + (3 << 8) + 2, &options).is_turn_back);
+        assert!(decode((1000 << 14) + (3 << 8), &options).is_manual);
+
+        let mut generator = CoreIdGenerator::default();
+        generator.init(options).unwrap();
+        let before = chrono::Utc::now().timestamp_millis();
+        let id = generator.next_id();
+        let decoded = generator.decode(id);
+        let after = chrono::Utc::now().timestamp_millis();
+        assert_eq!(decoded.worker_id, 3);
+        assert_eq!(decoded.seq, 5);
+        assert!(decoded.timestamp >= before && decoded.timestamp <= after);
+        assert_eq!(
+            decoded.datetime().unwrap().timestamp_millis(),
+            decoded.timestamp
+        );
+    }
+
+    #[test]
+    fn test_classic_method() {
+        let mut set: HashSet<i64> = HashSet::new();
+        let options = IdGeneratorOptions::new()
+            .method(2)
+            .worker_id(1)
+            .worker_id_bit_len(6)
+            .seq_bit_len(3);
+        let mut generator = CoreIdGenerator::default();
+        generator.init(options).unwrap();
+        assert_eq!(generator.get_options().method, Some(2));
+        let mut last_id = 0;
+        for _ in 0..3000 {
+            let new_id = generator.next_id();
+            let decoded = generator.decode(new_id);
+            assert!(decoded.timestamp <= chrono::Utc::now().timestamp_millis());
+            assert!(new_id > last_id);
+            assert!(set.insert(new_id), "Check fails! Same id!");
+            last_id = new_id;
+        }
+
+        // Setting options does not reuse the sequence of the last millisecond
+        for _ in 0..10 {
+            generator.set_options(IdGeneratorOptions::new()).unwrap();
+            for _ in 0..100 {
+                assert!(set.insert(generator.next_id()), "Check fails! Same id!");
+            }
+        }
+
+        // Neither does a new lock-free generator
+        let before = chrono::Utc::now().timestamp_millis() - 1582136402000;
+        let options = IdGeneratorOptions::new().method(2);
+        let generator = AtomicIdGenerator::new(options.clone()).unwrap();
+        assert!(decode(generator.next_id(), &options).time_tick > before);
+    }
+
+    #[test]
+    fn test_clock() {
+        let base_time = 1582136402000;
+        let clock = ManualClock::new(base_time + 10000);
+        let mut generator = CoreIdGenerator::with_clock(clock.clone());
+        let options = IdGeneratorOptions::new().worker_id(1).worker_id_bit_len(6);
+        generator.init(options).unwrap();
+
+        let id = generator.next_id();
+        let decoded = generator.decode(id);
+        assert_eq!((decoded.time_tick, decoded.seq), (10000, 5));
+
+        // Time turned back, ids are taken from the reserved turn back sequence
+        clock.set(base_time + 9995);
+        let id = generator.next_id();
+        let decoded = generator.decode(id);
+        assert_eq!((decoded.time_tick, decoded.seq), (9999, 1));
+        assert!(decoded.is_turn_back);
+        let id = generator.next_id();
+        let decoded = generator.decode(id);
+        assert_eq!((decoded.time_tick, decoded.seq), (9998, 1));
+
+        // Time caught up
+        clock.advance(6);
+        let id = generator.next_id();
+        let decoded = generator.decode(id);
+        assert_eq!((decoded.time_tick, decoded.seq), (10001, 5));
+
+        // Base time can not exceed the time of the clock
+        let options = IdGeneratorOptions::new().base_time(base_time + 20000);
+        assert_eq!(
+            generator.set_options(options),
+            Err(OptionError::InvalidBaseTime)
+        );
+
+        let clock = MonotonicClock::new();
+        let now = chrono::Utc::now().timestamp_millis();
+        assert!((clock.now_millis() - now).abs() < 1000);
+        assert!(SystemClock.now_millis() >= now);
+    }
+
+    #[test]
+    fn test_atomic_generator() {
+        let options = IdGeneratorOptions::new()
+            .worker_id(1)
+            .worker_id_bit_len(6)
+            .seq_bit_len(12);
+        let generator = AtomicIdGenerator::new(options.clone()).unwrap();
+        assert_eq!(
+            generator.get_options(),
+            IdGeneratorOptions {
+                method: Some(1),
+                base_time: Some(1582136402000),
+                time_unit: Some(TimeUnit::Millisecond),
+                worker_id: Some(1),
+                worker_id_bit_len: Some(6),
+                datacenter_id: Some(0),
+                datacenter_id_bit_len: Some(0),
+                seq_bit_len: Some(12),
+                max_seq_num: Some(4095),
+                min_seq_num: Some(5),
+                top_over_cost_count: Some(2000),
+                min_horizon_years: Some(0),
+                startup_strategy: Some(StartupStrategy::Sleep),
+                id_width: Some(IdWidth::Bits63),
+                layout: None,
+            }
+        );
+        let ids: Vec<Vec<i64>> = std::thread::scope(|s| {
+            let handles: Vec<_> = (0..4)
+                .map(|_| s.spawn(|| (0..100000).map(|_| generator.next_id()).collect()))
+                .collect();
+            handles.into_iter().map(|h| h.join().unwrap()).collect()
+        });
+        let mut set: HashSet<i64> = HashSet::new();
+        for thread_ids in ids {
+            assert!(thread_ids.windows(2).all(|w| w[0] < w[1]));
+            for new_id in thread_ids {
+                assert!(set.insert(new_id), "Check fails! Same id!");
+                let decoded = decode(new_id, &options);
+                assert_eq!(decoded.worker_id, 1);
+                assert!(decoded.seq >= 5);
+            }
+        }
+
+        // A failed call consumes nothing
+        let base_time = 1582136402000;
+        let options = IdGeneratorOptions::new()
+            .worker_id_bit_len(18)
+            .seq_bit_len(3)
+            .startup_strategy(StartupStrategy::Immediate);
+        let clock = ManualClock::new(base_time + (1 << 42) - 1);
+        let generator = AtomicIdGenerator::with_clock(options, clock).unwrap();
+        for seq in 5..8 {
+            assert_eq!(generator.try_next_id().unwrap() & 0b111, seq);
+        }
+        assert_eq!(
+            generator.try_next_id(),
+            Err(GenerateError::TimeTickOverflow((1 << 42) - 1))
+        );
+        assert_eq!(
+            generator.try_next_id(),
+            Err(GenerateError::TimeTickOverflow((1 << 42) - 1))
+        );
+        assert_eq!(generator.next_id() & 0b111, 5);
+    }
+
+    #[test]
+    fn test_batch() {
+        let mut generator = CoreIdGenerator::default();
+        let options = IdGeneratorOptions::new().worker_id(1).worker_id_bit_len(6);
+        generator.init(options).unwrap();
+        let ids = generator.next_ids(100000);
+        assert_eq!(ids.len(), 100000);
+        assert!(ids.windows(2).all(|w| w[0] < w[1]));
+        for id in ids.iter().step_by(97) {
+            let decoded = generator.decode(*id);
+            assert_eq!(decoded.worker_id, 1);
+            assert!(decoded.seq >= 5 && decoded.seq <= 255);
+        }
+        let mut more_ids = [0; 1000];
+        generator.fill_ids(&mut more_ids);
+        assert!(more_ids[0] > ids[ids.len() - 1]);
+        assert!(more_ids.windows(2).all(|w| w[0] < w[1]));
+
+        let mut set: HashSet<i64> = HashSet::new();
+        for id in IdInstance::next_ids(10000) {
+            assert!(set.insert(id), "Check fails! Same id!");
+        }
+        let mut ids = vec![0; 10000];
+        IdInstance::fill_ids(&mut ids);
+        for id in ids {
+            assert!(set.insert(id), "Check fails! Same id!");
+        }
+    }
+
+    #[test]
+    fn test_lease_block() {
+        let mut generator = CoreIdGenerator::default();
+        let options = IdGeneratorOptions::new().worker_id(1).worker_id_bit_len(6);
+        generator.init(options).unwrap();
+        let block = generator.lease_block(1000);
+        assert_eq!(block.remaining(), 1000);
+        let next_id = generator.next_id();
+        let mut set: HashSet<i64> = HashSet::new();
+        for id in block {
+            assert!(id < next_id);
+            assert!(set.insert(id), "Check fails! Same id!");
+        }
+        assert_eq!(set.len(), 1000);
+
+        let mut block = IdInstance::lease_block(10);
+        assert_eq!(block.len(), 10);
+        let first_id = block.next().unwrap();
+        assert_eq!(block.remaining(), 9);
+        assert!(block.all(|id| id > first_id));
+        assert!(generator.lease_block(0).is_empty());
+    }
+
+    #[test]
+    fn test_try_next_id() {
+        let base_time = 1582136402000;
+        let clock = ManualClock::new(base_time + 10000);
+        let mut generator = CoreIdGenerator::with_clock(clock.clone());
+        let options = IdGeneratorOptions::new().worker_id(1).worker_id_bit_len(6);
+        generator.init(options).unwrap();
+        assert!(generator.try_next_id().is_ok());
+
+        // Every time turned back takes the next index of the reserved turn back sequence,
+        // the fifth one would reuse the first index in the same time tick
+        for _ in 0..4 {
+            clock.set(base_time + 9995);
+            let id = generator.try_next_id().unwrap();
+            assert_eq!(generator.decode(id).time_tick, 9999);
+            clock.set(base_time + 10000);
+            assert!(generator.try_next_id().is_ok());
+        }
+        clock.set(base_time + 9995);
+        assert_eq!(
+            generator.try_next_id(),
+            Err(GenerateError::ClockMovedBackwards(5))
+        );
+
+        // Time tick exceeding 63 - 14 bits
+        clock.set(base_time + (1 << 49));
+        assert_eq!(
+            generator.try_next_id(),
+            Err(GenerateError::TimeTickOverflow((1 << 49) - 1))
+        );
+        let generator = AtomicIdGenerator::with_clock(
+            IdGeneratorOptions::new().worker_id_bit_len(6),
+            ManualClock::new(base_time + (1 << 49)),
+        )
+        .unwrap();
+        assert_eq!(
+            generator.try_next_id(),
+            Err(GenerateError::TimeTickOverflow((1 << 49) - 1))
+        );
+
+        assert!(IdInstance::try_next_id().is_ok());
+    }
+
+    #[test]
+    fn test_vec_instance_try_next_id() {
+        let options = vec![IdGeneratorOptions::new().worker_id(1).worker_id_bit_len(6)];
+        IdVecInstance::init(options).unwrap();
+        assert!(IdVecInstance::try_next_id(0).is_ok());
+        assert_eq!(
+            IdVecInstance::try_next_id(5),
+            Err(GenerateError::IndexOutOfRange(5))
+        );
+    }
+
+    #[test]
+    fn test_horizon() {
+        let options = IdGeneratorOptions::new()
+            .worker_id_bit_len(6)
+            .seq_bit_len(16);
+        let horizon = options.horizon().unwrap();
+        assert_eq!(horizon.last_timestamp, 1582136402000 + (1 << 41) - 1);
+        assert_eq!(
+            horizon.last_datetime().unwrap().timestamp_millis(),
+            horizon.last_timestamp
+        );
+        assert!(horizon.remaining_years > 60.0 && horizon.remaining_years < 70.0);
+        assert_eq!(horizon.ids_per_tick, 65531);
+        assert_eq!(horizon.ids_per_second, 65531 * 1000);
+        assert_eq!(horizon.max_burst_ids, 65531 * 2000);
+
+        let options = IdGeneratorOptions::new().method(2).max_seq_num(100);
+        let horizon = options.horizon().unwrap();
+        assert_eq!(horizon.ids_per_tick, 96);
+        assert_eq!(horizon.ids_per_second, 96000);
+        assert_eq!(horizon.max_burst_ids, 0);
+        let options = IdGeneratorOptions::new().time_unit(TimeUnit::Second);
+        let horizon = options.horizon().unwrap();
+        assert_eq!((horizon.ids_per_tick, horizon.ids_per_second), (251, 251));
+        assert_eq!(
+            IdGeneratorOptions::new().method(3).horizon(),
+            Err(OptionError::InvalidMethod)
+        );
+
+        let mut generator = CoreIdGenerator::default();
+        let options = IdGeneratorOptions::new()
+            .worker_id_bit_len(6)
+            .seq_bit_len(16)
+            .min_horizon_years(100);
+        assert!(matches!(
+            generator.init(options),
+            Err(OptionError::InsufficientHorizon(_))
+        ));
+        let options = IdGeneratorOptions::new().min_horizon_years(100);
+        generator.init(options).unwrap();
+        assert_eq!(generator.horizon().ids_per_tick, 251);
+    }
+
+    #[test]
+    fn test_registry() {
+        let registry: IdRegistry = IdRegistry::new();
+        assert!(registry.is_empty());
+        registry
+            .register(
+                "orders".to_string(),
+                IdGeneratorOptions::new().worker_id(1).worker_id_bit_len(6),
+            )
+            .unwrap();
+        registry
+            .register(
+                "users".to_string(),
+                IdGeneratorOptions::new().worker_id(2).worker_id_bit_len(6),
+            )
+            .unwrap();
+        assert_eq!(
+            registry.register("users".to_string(), IdGeneratorOptions::new()),
+            Err(OptionError::DuplicateKey("\"users\"".to_string()))
+        );
+        assert_eq!(registry.len(), 2);
+        assert!(registry.contains("orders"));
+
+        let mut set: HashSet<i64> = HashSet::new();
+        for _ in 0..10000 {
+            assert!(set.insert(registry.next_id("orders").unwrap()));
+            assert!(set.insert(registry.next_id("users").unwrap()));
+        }
+        let id = registry.next_id("users").unwrap();
+        let options = registry.get_options("users").unwrap();
+        assert_eq!(decode(id, &options).worker_id, 2);
+
+        registry
+            .set_options("orders", IdGeneratorOptions::new().seq_bit_len(12))
+            .unwrap();
+        assert_eq!(
+            registry.get_options("orders").unwrap().seq_bit_len,
+            Some(12)
+        );
+        let mut keys = registry.keys();
+        keys.sort();
+        assert_eq!(keys, vec!["orders".to_string(), "users".to_string()]);
+        assert_eq!(registry.iter().count(), 2);
+
+        registry.unregister("orders").unwrap();
+        assert_eq!(
+            registry.next_id("orders"),
+            Err(GenerateError::KeyNotFound("\"orders\"".to_string()))
+        );
+        assert_eq!(
+            registry.unregister("orders"),
+            Err(OptionError::KeyNotFound("\"orders\"".to_string()))
+        );
+        assert_eq!(registry.next_ids("users", 10).unwrap().len(), 10);
+        assert_eq!(registry.lease_block("users", 10).unwrap().len(), 10);
+
+        let registry: IdRegistry<u8> = IdRegistry::new();
+        registry.register(7, IdGeneratorOptions::new()).unwrap();
+        assert!(registry.next_id(&7).is_ok());
+    }
+
+    #[test]
+    fn test_generator_handle() {
+        fn assert_send_sync<T: Send + Sync>() {}
+        assert_send_sync::<IdGenerator>();
+
+        let options = IdGeneratorOptions::new().worker_id(1).worker_id_bit_len(6);
+        let generator = IdGenerator::new(options).unwrap();
+        let other = IdGenerator::new(IdGeneratorOptions::new().worker_id(2)).unwrap();
+        assert_eq!(generator.get_options().worker_id, Some(1));
+        assert_eq!(other.get_options().worker_id, Some(2));
+
+        // Clones share the same generator
+        let ids: Vec<Vec<i64>> = std::thread::scope(|s| {
+            let handles: Vec<_> = (0..4)
+                .map(|_| {
+                    let generator = generator.clone();
+                    s.spawn(move || (0..50000).map(|_| generator.next_id()).collect())
+                })
+                .collect();
+            handles.into_iter().map(|h| h.join().unwrap()).collect()
+        });
+        let mut set: HashSet<i64> = HashSet::new();
+        for new_id in ids.into_iter().flatten() {
+            assert!(set.insert(new_id), "Check fails! Same id!");
+        }
+        generator
+            .set_options(IdGeneratorOptions::new().seq_bit_len(12))
+            .unwrap();
+        assert_eq!(generator.clone().get_options().seq_bit_len, Some(12));
+        let id = generator.try_next_id().unwrap();
+        assert_eq!(generator.decode(id).worker_id, 1);
+
+        let generator: IdGenerator = CoreIdGenerator::with_clock(MonotonicClock::new()).into();
+        assert!(generator.next_ids(10).windows(2).all(|w| w[0] < w[1]));
+        assert!(IdInstance::generator().try_next_id().is_ok());
+    }
+
+    #[test]
+    fn test_startup_strategy() {
+        let options = IdGeneratorOptions::new()
+            .worker_id(1)
+            .worker_id_bit_len(6)
+            .startup_strategy(StartupStrategy::WaitLastTick);
+        let start = Instant::now();
+        let generators = (0..64)
+            .map(|_| IdGenerator::new(options.clone()).unwrap())
+            .collect::<Vec<_>>();
+        assert!(start.elapsed().as_millis() < 500);
+
+        // Ids generated after setting options never fall before the last time tick
+        let generator = &generators[0];
+        let last_tick = generator.decode(generator.lease_block(3000).last().unwrap());
+        generator
+            .set_options(IdGeneratorOptions::new().seq_bit_len(12))
+            .unwrap();
+        let decoded = generator.decode(generator.next_id());
+        assert!(decoded.time_tick >= last_tick.time_tick);
+
+        // Time ticks generated before are kept when changing the base time
+        generator
+            .set_options(IdGeneratorOptions::new().base_time(1582136403000))
+            .unwrap();
+        let new_decoded = generator.decode(generator.next_id());
+        assert!(new_decoded.timestamp >= decoded.timestamp);
+
+        // A running generator keeps generating after its own ids when setting options, without waiting
+        let clock = ManualClock::new(1582136402000 + 10000);
+        let mut generator = CoreIdGenerator::with_clock(clock);
+        let options = IdGeneratorOptions::new().startup_strategy(StartupStrategy::Immediate);
+        generator.init(options.clone()).unwrap();
+        let mut ids = HashSet::new();
+        for _ in 0..10 {
+            generator.init(options.clone()).unwrap();
+            generator
+                .set_options(IdGeneratorOptions::new().top_over_cost_count(1000))
+                .unwrap();
+            for _ in 0..100 {
+                assert!(ids.insert(generator.next_id()));
+            }
+        }
+        let generator = IdGenerator::new(IdGeneratorOptions::new()).unwrap();
+        let start = Instant::now();
+        generator
+            .set_options(IdGeneratorOptions::new().seq_bit_len(10))
+            .unwrap();
+        assert!(start.elapsed().as_millis() < 500);
+        // Switching to another worker waits again
+        generator
+            .set_options(IdGeneratorOptions::new().worker_id(2))
+            .unwrap();
+        assert!(start.elapsed().as_millis() >= 500);
+
+        let options = IdGeneratorOptions::new().startup_strategy(StartupStrategy::Immediate);
+        let start = Instant::now();
+        let generator = AtomicIdGenerator::new(options).unwrap();
+        assert!(start.elapsed().as_millis() < 500);
+        assert_eq!(
+            generator.get_options().startup_strategy,
+            Some(StartupStrategy::Immediate)
+        );
+    }
+
+    #[test]
+    fn test_tick_store() {
+        let path = std::env::temp_dir().join(format!(
+            "idgenerator-tick-store-{}-{}",
+            std::process::id(),
+            chrono::Utc::now().timestamp_millis()
+        ));
+        let store = FileTickStore::new(&path);
+        assert_eq!(store.load().unwrap(), None);
+
+        let base_time = 1582136402000;
+        let options = IdGeneratorOptions::new()
+            .worker_id(1)
+            .worker_id_bit_len(6)
+            .startup_strategy(StartupStrategy::Immediate);
+        let clock = ManualClock::new(base_time + 10000);
+        let mut generator = CoreIdGenerator::with_clock(clock.clone());
+        generator.set_tick_store(store.clone(), std::time::Duration::from_secs(1));
+        generator.init(options.clone()).unwrap();
+        let id = generator.next_id();
+        assert_eq!(generator.decode(id).time_tick, 10000);
+        assert_eq!(store.load().unwrap(), Some(base_time + 11000));
+        clock.advance(500);
+        generator.next_id();
+        assert_eq!(store.load().unwrap(), Some(base_time + 11000));
+        clock.advance(500);
+        generator.try_next_id().unwrap();
+        assert_eq!(store.load().unwrap(), Some(base_time + 12000));
+
+        // Restart with the clock set backwards
+        let clock = ManualClock::new(base_time + 5000);
+        let mut generator = CoreIdGenerator::with_clock(clock.clone());
+        generator.set_tick_store(store.clone(), std::time::Duration::from_secs(1));
+        assert_eq!(
+            generator.init(options.clone()),
+            Err(OptionError::ClockBehindTickStore(7000))
+        );
+        clock.set(base_time + 12001);
+        let mut generator = CoreIdGenerator::with_clock(clock.clone());
+        generator.set_tick_store(store.clone(), std::time::Duration::from_secs(1));
+        generator.init(options.clone()).unwrap();
+        let id = generator.next_id();
+        assert_eq!(generator.decode(id).time_tick, 12001);
+
+        // A failed init leaves the running generator unchanged
+        assert_eq!(
+            generator.init(options.clone().worker_id(2)),
+            Err(OptionError::ClockBehindTickStore(1000))
+        );
+        assert_eq!(generator.get_options().worker_id, Some(1));
+        let id = generator.next_id();
+        let decoded = generator.decode(id);
+        assert_eq!((decoded.worker_id, decoded.time_tick), (1, 12001));
+
+        // No id is generated past the persisted time while saving fails
+        let dir = path.with_extension("dir");
+        let failing_store = FileTickStore::new(dir.join("tick"));
+        let mut generator = CoreIdGenerator::with_clock(clock.clone());
+        generator.set_tick_store(failing_store, std::time::Duration::from_secs(1));
+        generator.init(options.clone()).unwrap();
+        assert!(matches!(
+            generator.try_next_id(),
+            Err(GenerateError::TickStore(_))
+        ));
+        let creator = {
+            let dir = dir.clone();
+            std::thread::spawn(move || {
+                std::thread::sleep(std::time::Duration::from_millis(50));
+                std::fs::create_dir(dir).unwrap();
+            })
+        };
+        let id = generator.next_id();
+        let decoded = generator.decode(id);
+        assert_eq!((decoded.time_tick, decoded.seq), (12001, 5));
+        creator.join().unwrap();
+        std::fs::remove_dir_all(dir).unwrap();
+
+        // A quick restart waits until the clock passes the persisted time, ahead by the checkpoint interval
+        let interval = std::time::Duration::from_millis(300);
+        let mut generator = CoreIdGenerator::default();
+        generator.set_tick_store(store.clone(), interval);
+        generator.init(IdGeneratorOptions::new()).unwrap();
+        let id = generator.next_id();
+        let persisted = store.load().unwrap().unwrap();
+        let mut generator = CoreIdGenerator::default();
+        generator.set_tick_store(store.clone(), interval);
+        generator.init(IdGeneratorOptions::new()).unwrap();
+        let resumed = generator.next_id();
+        assert!(generator.decode(resumed).timestamp > persisted);
+        assert!(resumed > id);
+        // The persisted time further ahead means the clock has been set backwards
+        store
+            .save(chrono::Utc::now().timestamp_millis() + 10000)
+            .unwrap();
+        let mut generator = CoreIdGenerator::default();
+        generator.set_tick_store(store.clone(), interval);
+        assert!(matches!(
+            generator.init(IdGeneratorOptions::new()),
+            Err(OptionError::ClockBehindTickStore(_))
+        ));
+
+        // Wait until the clock passes the persisted time
+        let mut generator = CoreIdGenerator::default();
+        store
+            .save(chrono::Utc::now().timestamp_millis() + 200)
+            .unwrap();
+        generator.set_tick_store(store, std::time::Duration::from_secs(1));
+        let options = IdGeneratorOptions::new().startup_strategy(StartupStrategy::WaitLastTick);
+        generator.init(options).unwrap();
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_file_lock_allocator() {
+        let dir = std::env::temp_dir().join(format!(
+            "idgenerator-allocator-{}-{}",
+            std::process::id(),
+            chrono::Utc::now().timestamp_millis()
+        ));
+        let allocator = FileLockAllocator::new(&dir);
+        let mut leases: Vec<_> = (0..4).map(|_| allocator.allocate(2).unwrap()).collect();
+        let worker_ids: Vec<u32> = leases.iter().map(|lease| lease.worker_id()).collect();
+        assert_eq!(worker_ids, vec![0, 1, 2, 3]);
+        assert_eq!(
+            allocator.allocate(2).unwrap_err(),
+            AllocateError::Exhausted(3)
+        );
+
+        // Released when dropped
+        leases.remove(1);
+        let lease = allocator.allocate(2).unwrap();
+        assert_eq!(lease.worker_id(), 1);
+        assert!(lease.is_valid());
+        let options = lease.apply(IdGeneratorOptions::new().worker_id_bit_len(2));
+        assert_eq!(options.worker_id, Some(1));
+        assert!(IdGenerator::new(options).is_ok());
+
+        assert_eq!(
+            allocator.allocate(0).unwrap_err(),
+            AllocateError::Option(OptionError::InvalidWorkerIdBitLen(
+                "should have worker_id_bit_len in range [1, 21]".to_string()
+            ))
+        );
+        drop(lease);
+        drop(leases);
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_lease_lost() {
+        use std::sync::atomic::{AtomicBool, Ordering};
+        use std::sync::Arc;
+
+        #[derive(Debug)]
+        struct FlagLease(AtomicBool);
+
+        impl WorkerIdLease for FlagLease {
+            fn worker_id(&self) -> u32 {
+                0
+            }
+
+            fn is_valid(&self) -> bool {
+                self.0.load(Ordering::SeqCst)
+            }
+        }
+
+        let options = IdGeneratorOptions::new().startup_strategy(StartupStrategy::Immediate);
+        let generator = IdGenerator::new(options.clone()).unwrap();
+        let lease = Arc::new(FlagLease(AtomicBool::new(false)));
+        generator.set_lease(lease.clone());
+        assert_eq!(generator.try_next_id(), Err(GenerateError::LeaseLost(0)));
+
+        // The handle blocks until a valid lease is set, instead of panicking
+        let setter = {
+            let generator = generator.clone();
+            std::thread::spawn(move || {
+                std::thread::sleep(std::time::Duration::from_millis(50));
+                generator.set_lease(Arc::new(FlagLease(AtomicBool::new(true))));
+            })
+        };
+        let start = Instant::now();
+        let ids = generator.next_ids(10);
+        assert!(start.elapsed().as_millis() >= 50);
+        assert_eq!(ids.iter().collect::<HashSet<_>>().len(), 10);
+        setter.join().unwrap();
+        assert!(generator.try_next_id().is_ok());
+
+        // No new lease can be set while a core generator is borrowed, so it panics instead
+        let mut core = CoreIdGenerator::default();
+        core.init(options).unwrap();
+        core.set_lease(lease);
+        let panicked = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| core.next_id()));
+        assert!(panicked.is_err());
+    }
+
+    #[test]
+    fn test_datacenter_id() {
+        let options = IdGeneratorOptions::new()
+            .datacenter_id(5)
+            .datacenter_id_bit_len(3)
+            .worker_id(9)
+            .worker_id_bit_len(6)
+            .seq_bit_len(10)
+            .startup_strategy(StartupStrategy::Immediate);
+        let generator = IdGenerator::new(options.clone()).unwrap();
+        let options = generator.get_options();
+        assert_eq!(
+            (options.datacenter_id, options.datacenter_id_bit_len),
+            (Some(5), Some(3))
+        );
+        let id = generator.next_id();
+        assert_eq!((id >> 16) & 0b111, 5);
+        let decoded = generator.decode(id);
+        assert_eq!((decoded.datacenter_id, decoded.worker_id), (5, 9));
+        let decoded = decode(
+            AtomicIdGenerator::new(options.clone()).unwrap().next_id(),
+            &options,
+        );
+        assert_eq!((decoded.datacenter_id, decoded.worker_id), (5, 9));
+        assert_eq!(
+            generator.horizon().last_timestamp,
+            1582136402000 + (1 << (63 - 19)) - 1
+        );
+
+        assert_eq!(
+            IdGenerator::new(options.clone().datacenter_id_bit_len(7)).unwrap_err(),
+            OptionError::BitLenOverflow(
+                "should have datacenter_id_bit_len + worker_id_bit_len + seq_bit_len <= 22"
+                    .to_string()
+            )
+        );
+        assert_eq!(
+            IdGenerator::new(options.datacenter_id(8)).unwrap_err(),
+            OptionError::InvalidDatacenterId("should in range [0, 7]".to_string())
+        );
+    }
+
+    #[test]
+    fn test_id_width() {
+        // The time tick takes the sign bit of 64-bit ids
+        let base_time = 1582136402000;
+        let clock = ManualClock::new(base_time + (1 << 49) + 10);
+        let mut generator = CoreIdGenerator::with_clock(clock.clone());
+        let options = IdGeneratorOptions::new()
+            .worker_id(1)
+            .worker_id_bit_len(6)
+            .startup_strategy(StartupStrategy::Immediate);
+        generator.init(options.clone()).unwrap();
+        assert_eq!(
+            generator.try_next_id(),
+            Err(GenerateError::TimeTickOverflow((1 << 49) - 1))
+        );
+        let options = options.id_width(IdWidth::Bits64);
+        generator.set_options(options.clone()).unwrap();
+        let id = generator.try_next_u64().unwrap();
+        assert_eq!(id >> 63, 1);
+        let decoded = decode(id as i64, &options);
+        assert_eq!((decoded.time_tick, decoded.worker_id), ((1 << 49) + 10, 1));
+
+        let options = IdGeneratorOptions::new()
+            .id_width(IdWidth::Bits128)
+            .datacenter_id(3)
+            .datacenter_id_bit_len(2)
+            .worker_id(1000)
+            .worker_id_bit_len(14)
+            .seq_bit_len(20)
+            .startup_strategy(StartupStrategy::Immediate);
+        let generator = IdGenerator::new(options.clone()).unwrap();
+        let ids: Vec<u128> = (0..1000).map(|_| generator.next_u128()).collect();
+        assert!(ids.windows(2).all(|pair| pair[0] < pair[1]));
+        assert_eq!((ids[0] >> 64) as u16, (3 << 14) + 1000);
+        let decoded = decode_u128(ids[0], &options);
+        assert_eq!(
+            (decoded.datacenter_id, decoded.worker_id, decoded.seq),
+            (3, 1000, 5)
+        );
+        assert!(decoded.datetime().is_some());
+        assert_eq!(generator.try_next_id(), Err(GenerateError::IdTooWide));
+        assert_eq!(generator.try_next_u64(), Err(GenerateError::IdTooWide));
+        let panicked =
+            std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| generator.next_ids(2)));
+        assert!(panicked.is_err());
+        assert_eq!(generator.decode(1), decode_u128(1, &options));
+        assert_eq!(
+            generator.horizon().last_timestamp,
+            base_time + (1 << 48) - 1
+        );
+
+        assert_eq!(
+            IdGenerator::new(options.clone().worker_id_bit_len(15)).unwrap_err(),
+            OptionError::BitLenOverflow(
+                "should have datacenter_id_bit_len + worker_id_bit_len <= 16 for 128-bit ids"
+                    .to_string()
+            )
+        );
+        assert!(matches!(
+            IdGenerator::new(options.clone().layout(IdLayout::sonyflake())),
+            Err(OptionError::InvalidLayout(_))
+        ));
+        assert!(matches!(
+            AtomicIdGenerator::new(options),
+            Err(OptionError::InvalidIdWidth(_))
+        ));
+
+        // The 64-bit ids are widened
+        let generator = IdGenerator::new(
+            IdGeneratorOptions::new().startup_strategy(StartupStrategy::Immediate),
+        )
+        .unwrap();
+        let id = generator.next_u128();
+        assert!(id < 1 << 63);
+        assert_eq!(generator.decode(id as i64).seq, 5);
+    }
+
+    #[test]
+    fn test_layout() {
+        let options = IdGeneratorOptions::new()
+            .layout(IdLayout::sonyflake())
+            .worker_id(300)
+            .startup_strategy(StartupStrategy::Immediate);
+        let generator = IdGenerator::new(options).unwrap();
+        let options = generator.get_options();
+        assert_eq!(
+            (options.worker_id_bit_len, options.seq_bit_len),
+            (Some(16), Some(8))
+        );
+        let ids = generator.next_ids(1000);
+        assert_eq!(ids.iter().collect::<HashSet<_>>().len(), 1000);
+        assert!(ids.windows(2).all(|pair| pair[0] < pair[1]));
+        let id = ids[0];
+        assert_eq!((id & 0xffff, (id >> 16) & 0xff), (300, 5));
+        let decoded = generator.decode(id);
+        assert_eq!((decoded.worker_id, decoded.seq), (300, 5));
+        assert_eq!(
+            generator.horizon().last_timestamp,
+            1582136402000 + (1 << 39) - 1
+        );
+
+        let options = IdGeneratorOptions::new()
+            .layout(IdLayout::instagram(1234))
+            .startup_strategy(StartupStrategy::Immediate);
+        let generator = AtomicIdGenerator::new(options.clone()).unwrap();
+        let id = generator.next_id();
+        assert_eq!(((id >> 10) & 0x1fff, id & 0x3ff), (1234, 5));
+        let decoded = decode(id, &options);
+        assert_eq!(decoded.custom, vec![("shard".to_string(), 1234)]);
+        assert_eq!(decoded.seq, 5);
+        assert_eq!(
+            AtomicIdGenerator::new(options.worker_id(1)).unwrap_err(),
+            OptionError::InvalidWorkerId(
+                "should be 0 without a worker field in the layout".to_string()
+            )
+        );
+
+        let invalid = |layout: IdLayout| IdGenerator::new(IdGeneratorOptions::new().layout(layout));
+        assert!(matches!(
+            invalid(IdLayout::new().timestamp(41).worker(10)),
+            Err(OptionError::InvalidLayout(_))
+        ));
+        assert!(matches!(
+            invalid(IdLayout::new().timestamp(50).worker(10).sequence(10)),
+            Err(OptionError::InvalidLayout(_))
+        ));
+        assert!(matches!(
+            invalid(
+                IdLayout::new()
+                    .timestamp(41)
+                    .custom("type", 2, 4)
+                    .sequence(10)
+            ),
+            Err(OptionError::InvalidLayout(_))
+        ));
+    }
+
+    #[test]
+    fn test_time_unit() {
+        let base_time = 1582136402000;
+        let clock = ManualClock::new(base_time + 12345);
+        let mut generator = CoreIdGenerator::with_clock(clock.clone());
+        let options = IdGeneratorOptions::new()
+            .worker_id(1)
+            .worker_id_bit_len(6)
+            .time_unit(TimeUnit::TenMilliseconds)
+            .startup_strategy(StartupStrategy::Immediate);
+        generator.init(options.clone()).unwrap();
+        let id = generator.next_id();
+        let decoded = generator.decode(id);
+        assert_eq!((decoded.time_tick, decoded.seq), (1234, 5));
+        assert_eq!(decoded.timestamp, base_time + 12340);
+        assert_eq!(
+            generator.horizon().last_timestamp,
+            base_time + ((1 << (63 - 14)) - 1) * 10
+        );
+
+        // The time ticks generated before are kept under the coarser unit
+        clock.advance(30);
+        generator
+            .set_options(IdGeneratorOptions::new().time_unit(TimeUnit::Second))
+            .unwrap();
+        let id = generator.next_id();
+        let decoded = generator.decode(id);
+        assert_eq!(decoded.time_tick, 12);
+        assert_eq!(decoded.timestamp, base_time + 12000);
+
+        let decoded = decode(
+            AtomicIdGenerator::with_clock(options.clone(), clock.clone())
+                .unwrap()
+                .next_id(),
+            &options,
+        );
+        assert_eq!(decoded.time_tick, 1237);
+
+        // Base time should be a multiple of the time unit
+        assert_eq!(
+            IdGenerator::new(options.base_time(base_time + 5)).unwrap_err(),
+            OptionError::InvalidBaseTime
+        );
+    }
+
+    #[test]
+    fn test_worker_id_source() {
+        use crate::allocator::source::read_machine_id;
+        use crate::allocator::{hash_worker_id, ipv4_worker_id, ordinal_worker_id};
+        use std::net::Ipv4Addr;
+
+        assert_eq!(ordinal_worker_id("web-3", 6), Ok(3));
+        assert_eq!(
+            ordinal_worker_id("id-gen-12.id-gen.default.svc\n", 6),
+            Ok(12)
+        );
+        assert_eq!(
+            ordinal_worker_id("web-64", 6),
+            Err(OptionError::InvalidWorkerId(
+                "should in range [0, 63]".to_string()
+            ))
         );
+        assert!(matches!(
+            ordinal_worker_id("web", 6),
+            Err(OptionError::WorkerIdUnavailable(_))
+        ));
+        assert_eq!(ipv4_worker_id(Ipv4Addr::new(10, 0, 1, 77), 6), Ok(13));
+        assert_eq!(ipv4_worker_id(Ipv4Addr::new(10, 0, 1, 77), 10), Ok(333));
+        assert!(matches!(
+            ipv4_worker_id(Ipv4Addr::new(10, 0, 1, 77), 22),
+            Err(OptionError::InvalidWorkerIdBitLen(_))
+        ));
+        // Stable hash, the same on every platform
+        let machine_id = "0123456789abcdef0123456789abcdef";
+        assert_eq!(hash_worker_id(machine_id, 10), Ok(962));
+        assert_eq!(hash_worker_id(&format!("{machine_id}\n"), 10), Ok(962));
+        assert_eq!(hash_worker_id(machine_id, 6), Ok(962 & 63));
+        // An empty machine id falls through to the next path
+        let dir =
+            std::env::temp_dir().join(format!("idgenerator-machine-id-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let (empty, filled) = (dir.join("empty"), dir.join("filled"));
+        std::fs::write(&empty, "\n").unwrap();
+        std::fs::write(&filled, format!("{machine_id}\n")).unwrap();
+        let paths = [
+            dir.join("missing").to_str().unwrap().to_string(),
+            empty.to_str().unwrap().to_string(),
+            filled.to_str().unwrap().to_string(),
+        ];
+        let paths: Vec<&str> = paths.iter().map(String::as_str).collect();
+        assert_eq!(read_machine_id(&paths).unwrap().trim(), machine_id);
+        assert!(matches!(
+            read_machine_id(&paths[..2]),
+            Err(OptionError::WorkerIdUnavailable(_))
+        ));
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        let name = format!("IDGENERATOR_WORKER_ID_{}", std::process::id());
+        std::env::set_var(&name, "42");
+        let options = WorkerIdSource::Env(name.clone())
+            .apply(IdGeneratorOptions::new(), 6)
+            .unwrap();
+        assert_eq!(
+            (options.worker_id, options.worker_id_bit_len),
+            (Some(42), Some(6))
+        );
+        assert!(WorkerIdSource::Env(name.clone()).worker_id(5).is_err());
+        std::env::set_var(&name, "forty-two");
+        assert!(matches!(
+            WorkerIdSource::Env(name.clone()).worker_id(6),
+            Err(OptionError::WorkerIdUnavailable(_))
+        ));
+        std::env::remove_var(&name);
+    }
+
+    #[cfg(feature = "sqlite")]
+    #[test]
+    fn test_sql_lease_allocator() {
+        use crate::allocator::SqlLease;
+        use std::sync::Arc;
+        use std::time::Duration;
+
+        let path = std::env::temp_dir().join(format!(
+            "idgenerator-leases-{}-{}.db",
+            std::process::id(),
+            chrono::Utc::now().timestamp_millis()
+        ));
+        let connect = |owner: &str| {
+            let conn = rusqlite::Connection::open(&path).unwrap();
+            SqlLeaseAllocator::new(conn, owner, Duration::from_secs(10)).unwrap()
+        };
+        // Expiry is decided by the database clock alone, so leases are expired by rewinding their rows
+        let db = rusqlite::Connection::open(&path).unwrap();
+        let expire = |worker_id: u32| {
+            db.execute(
+                "UPDATE idgenerator_worker_leases SET expires_at = 0 WHERE worker_id = ?1",
+                [worker_id],
+            )
+            .unwrap();
+        };
+        let a = connect("a");
+        let b = connect("b");
+
+        let lease_a = a.allocate(1).unwrap();
+        let lease_b = b.allocate(1).unwrap();
+        assert_eq!((lease_a.worker_id(), lease_b.worker_id()), (0, 1));
+        assert_eq!(a.allocate(1).unwrap_err(), AllocateError::Exhausted(1));
+        let now = chrono::Utc::now().timestamp_millis();
+        assert!((lease_a.expires_at() - now - 10_000).abs() < 1_000);
+
+        // Renewed lease survives, the expired one is taken over
+        assert!(lease_a.renew().unwrap());
+        expire(1);
+        let taken_over: SqlLease = a.allocate(1).unwrap();
+        assert_eq!(taken_over.worker_id(), 1);
+        assert!(!lease_b.renew().unwrap());
+        assert!(!lease_b.is_valid());
+        drop(lease_b);
+        assert!(taken_over.renew().unwrap());
+        assert!(lease_a.is_valid());
+        drop((lease_a, taken_over));
+
+        // The host gives the lease up the safety margin before it expires
+        let short =
+            connect("s").with_safety_margin(Duration::from_secs(9) + Duration::from_millis(950));
+        let lease = short.allocate(1).unwrap();
+        assert!(lease.is_valid());
+        std::thread::sleep(Duration::from_millis(60));
+        assert!(!lease.is_valid());
+        drop(lease);
+
+        // Lost lease stops the generator until the heartbeat applies a new one
+        let lease = Arc::new(a.allocate(2).unwrap());
+        assert_eq!(lease.worker_id(), 0);
+        let options = IdGeneratorOptions::new()
+            .worker_id_bit_len(2)
+            .startup_strategy(StartupStrategy::Immediate);
+        let generator = IdGenerator::new(lease.apply(options)).unwrap();
+        let heartbeat = lease.keep_alive(generator.clone(), Duration::from_millis(5));
+        drop(lease);
+        // Another owner takes all the worker ids at once, as if the lease had expired
+        db.execute_batch(
+            "BEGIN IMMEDIATE;
+            DELETE FROM idgenerator_worker_leases;
+            INSERT INTO idgenerator_worker_leases VALUES
+                (0, 'c', 1 << 60), (1, 'c', 1 << 60), (2, 'c', 1 << 60), (3, 'c', 1 << 60);
+            COMMIT;",
+        )
+        .unwrap();
+        let now = Instant::now();
+        while generator.try_next_id() != Err(GenerateError::LeaseLost(0)) {
+            assert!(now.elapsed().as_secs() < 5);
+            std::thread::sleep(Duration::from_millis(5));
+        }
+        assert_eq!(heartbeat.worker_id(), 0);
+        db.execute(
+            "DELETE FROM idgenerator_worker_leases WHERE owner = 'c'",
+            [],
+        )
+        .unwrap();
+        let now = Instant::now();
+        while generator.try_next_id().is_err() {
+            assert!(now.elapsed().as_secs() < 5);
+            std::thread::sleep(Duration::from_millis(5));
+        }
+        drop(heartbeat);
+        drop(db);
+        std::fs::remove_file(path).unwrap();
+    }
+
+    /// Serve the commands used by `RedisLeaseAllocator` like a Redis server, with keys expiring by the clock
+    #[cfg(feature = "redis")]
+    fn spawn_redis_stand_in(clock: ManualClock) -> String {
+        use std::collections::HashMap;
+        use std::io::{BufRead, BufReader, Read, Write};
+        use std::net::{TcpListener, TcpStream};
+        use std::sync::{Arc, Mutex};
+
+        type Keys = Arc<Mutex<HashMap<String, (String, i64)>>>;
+
+        fn read_command(reader: &mut BufReader<TcpStream>) -> Option<Vec<String>> {
+            let mut line = String::new();
+            reader.read_line(&mut line).ok()?;
+            let len: usize = line.trim_end().strip_prefix('*')?.parse().ok()?;
+            let mut args = Vec::with_capacity(len);
+            for _ in 0..len {
+                line.clear();
+                reader.read_line(&mut line).ok()?;
+                let len: usize = line.trim_end().strip_prefix('$')?.parse().ok()?;
+                let mut arg = vec![0; len + 2];
+                reader.read_exact(&mut arg).ok()?;
+                arg.truncate(len);
+                args.push(String::from_utf8(arg).ok()?);
+            }
+            Some(args)
+        }
+
+        fn reply(args: &[String], keys: &Keys, now: i64) -> &'static str {
+            let mut keys = keys.lock().unwrap();
+            keys.retain(|_, (_, expires_at)| *expires_at > now);
+            match args[0].to_uppercase().as_str() {
+                // SET key owner NX PX milliseconds
+                "SET" if keys.contains_key(&args[1]) => "$-1\r\n",
+                "SET" => {
+                    let expires_at = now + args[5].parse::<i64>().unwrap();
+                    keys.insert(args[1].clone(), (args[2].clone(), expires_at));
+                    "+OK\r\n"
+                }
+                // EVAL script 1 key owner [milliseconds]
+                "EVAL" => match keys.get_mut(&args[3]) {
+                    Some((owner, expires_at)) if *owner == args[4] => {
+                        if args[1].contains("PEXPIRE") {
+                            *expires_at = now + args[5].parse::<i64>().unwrap();
+                        } else {
+                            keys.remove(&args[3]);
+                        }
+                        ":1\r\n"
+                    }
+                    _ => ":0\r\n",
+                },
+                _ => "-ERR unknown command\r\n",
+            }
+        }
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("redis://{}/", listener.local_addr().unwrap());
+        let keys = Keys::default();
+        std::thread::spawn(move || {
+            for stream in listener.incoming() {
+                let (keys, clock) = (keys.clone(), clock.clone());
+                let mut stream = stream.unwrap();
+                let mut reader = BufReader::new(stream.try_clone().unwrap());
+                std::thread::spawn(move || {
+                    while let Some(args) = read_command(&mut reader) {
+                        let reply = reply(&args, &keys, clock.now_millis());
+                        if stream.write_all(reply.as_bytes()).is_err() {
+                            break;
+                        }
+                    }
+                });
+            }
+        });
+        url
+    }
+
+    #[cfg(feature = "redis")]
+    #[test]
+    fn test_redis_lease_allocator() {
+        use std::sync::Arc;
+        use std::time::Duration;
+
+        let clock = ManualClock::new(1_700_000_000_000);
+        let client = redis::Client::open(spawn_redis_stand_in(clock.clone())).unwrap();
+        let a = RedisLeaseAllocator::new(client.clone(), "a", Duration::from_secs(60));
+        let b = RedisLeaseAllocator::new(client, "b", Duration::from_secs(60));
+
+        let lease_a = Arc::new(a.allocate(1).unwrap());
+        let lease_b = b.allocate(1).unwrap();
+        assert_eq!((lease_a.worker_id(), lease_b.worker_id()), (0, 1));
+        assert_eq!(a.allocate(1).unwrap_err(), AllocateError::Exhausted(1));
+        // Released when dropped
+        drop(lease_b);
+        assert_eq!(b.allocate(1).unwrap().worker_id(), 1);
+
+        let options = IdGeneratorOptions::new()
+            .worker_id_bit_len(1)
+            .startup_strategy(StartupStrategy::Immediate);
+        let generator = IdGenerator::new(lease_a.apply(options)).unwrap();
+        generator.set_lease(lease_a.clone());
+        assert!(lease_a.renew().unwrap());
+        assert!(generator.try_next_id().is_ok());
+
+        // The expired key is taken over, and the heartbeat finds the lease lost
+        let lost = lease_a.subscribe();
+        let heartbeat = lease_a.keep_alive(Duration::from_millis(5));
+        clock.advance(61_000);
+        assert_eq!(b.allocate(1).unwrap().worker_id(), 0);
+        assert_eq!(lost.recv_timeout(Duration::from_secs(5)), Ok(0));
+        assert!(!lease_a.is_valid());
+        assert_eq!(generator.try_next_id(), Err(GenerateError::LeaseLost(0)));
+        drop(heartbeat);
+    }
+
+    #[test]
+    fn test_encoding() {
+        let encodings = [Encoding::Base62, Encoding::Crockford32, Encoding::Base36];
+        for encoding in encodings {
+            for id in [0, 1, 61, 62, 1 << 40, i64::MAX as u64, u64::MAX] {
+                let encoded = encoding.encode(id);
+                let fixed = encoding.encode_fixed(id);
+                assert_eq!(fixed.len(), encoding.fixed_width());
+                assert!(fixed.ends_with(&encoded));
+                assert_eq!(encoding.decode(&encoded), Ok(id));
+                assert_eq!(encoding.decode(&fixed), Ok(id));
+            }
+            assert_eq!(encoding.decode(""), Err(EncodingError::Empty));
+            assert_eq!(
+                encoding.decode("0#"),
+                Err(EncodingError::InvalidChar('#', 1))
+            );
+        }
+        assert_eq!(Encoding::Base62.encode(u64::MAX), "LygHa16AHYF");
+        assert_eq!(Encoding::Crockford32.encode(u64::MAX), "FZZZZZZZZZZZZ");
+        assert_eq!(Encoding::Base36.encode(u64::MAX), "3w5e11264sgsf");
+        assert_eq!(
+            Encoding::Base62.decode("zzzzzzzzzzz"),
+            Err(EncodingError::Overflow)
+        );
+        assert_eq!(Encoding::Base62.decode("a"), Ok(36));
+        assert_eq!(Encoding::Base36.decode("A"), Ok(10));
+
+        // Crockford base32 is case-insensitive and reads the ambiguous characters as the digits they look like
+        assert_eq!(normalize_crockford32("o1l-Ia"), "0111A");
+        assert_eq!(
+            Encoding::Crockford32.decode("o1l-Ia"),
+            Encoding::Crockford32.decode("0111A")
+        );
+        assert_eq!(
+            Encoding::Crockford32.decode("U"),
+            Err(EncodingError::InvalidChar('U', 0))
+        );
+
+        // The fixed-width strings sort in the order of the ids
+        let generator = IdGenerator::new(
+            IdGeneratorOptions::new().startup_strategy(StartupStrategy::Immediate),
+        )
+        .unwrap();
+        for encoding in encodings {
+            let mut ids = generator.next_ids(100);
+            ids.push(7);
+            let mut encoded: Vec<String> = ids
+                .iter()
+                .map(|&id| encoding.encode_fixed(id as u64))
+                .collect();
+            encoded.sort();
+            ids.sort();
+            let decoded: Vec<i64> = encoded
+                .iter()
+                .map(|s| encoding.decode(s).unwrap() as i64)
+                .collect();
+            assert_eq!(decoded, ids);
+            let id = generator.next_id_string(encoding);
+            assert_eq!(id.len(), encoding.fixed_width());
+            assert!(encoding.decode(&id).unwrap() as i64 > ids[100]);
+        }
+    }
+
+    #[test]
+    fn test_obfuscator() {
+        let obfuscator = IdObfuscator::new(1, 0x5eed).unwrap();
+        let generator = IdGenerator::new(
+            IdGeneratorOptions::new().startup_strategy(StartupStrategy::Immediate),
+        )
+        .unwrap();
+        let mut ids = generator.next_ids(10000);
+        ids.extend([0, 1, i64::MAX]);
+        let mut permuted = HashSet::new();
+        for &id in &ids {
+            let opaque = obfuscator.permute(id).unwrap();
+            assert!(opaque >= 0);
+            assert!(permuted.insert(opaque));
+            assert_eq!(obfuscator.unpermute(1, opaque), Ok(id));
+
+            let obfuscated = obfuscator.obfuscate(id).unwrap();
+            assert_eq!(obfuscated.len(), 12);
+            assert!(obfuscated.starts_with('1'));
+            assert_eq!(obfuscator.reveal(&obfuscated), Ok(id));
+        }
+        // Consecutive ids are scattered
+        assert!(obfuscator.permute(ids[1]).unwrap() - obfuscator.permute(ids[0]).unwrap() != 1);
+
+        // Rotate the key, the ids obfuscated with the retired key are still revealed
+        let rotated = IdObfuscator::new(2, 0xbeef)
+            .unwrap()
+            .with_retired_key(1, 0x5eed)
+            .unwrap();
+        let obfuscated = obfuscator.obfuscate(ids[0]).unwrap();
+        assert_eq!(rotated.reveal(&obfuscated), Ok(ids[0]));
+        let rotated_obfuscated = rotated.obfuscate(ids[0]).unwrap();
+        assert!(rotated_obfuscated.starts_with('2'));
+        assert_ne!(rotated_obfuscated[1..], obfuscated[1..]);
+        assert_eq!(rotated.reveal(&rotated_obfuscated), Ok(ids[0]));
+        assert_eq!(
+            obfuscator.reveal(&rotated_obfuscated),
+            Err(ObfuscationError::UnknownVersion(2))
+        );
+
+        assert_eq!(
+            obfuscator.permute(-1),
+            Err(ObfuscationError::NegativeId(-1))
+        );
+        assert!(matches!(
+            obfuscator.reveal("1zzzzzzzzzzz"),
+            Err(ObfuscationError::Encoding(EncodingError::Overflow))
+        ));
+        assert!(matches!(
+            obfuscator.reveal("1abc"),
+            Err(ObfuscationError::InvalidId(_))
+        ));
+        assert_eq!(
+            IdObfuscator::new(62, 0).unwrap_err(),
+            ObfuscationError::InvalidVersion(62)
+        );
+        assert_eq!(
+            rotated.with_retired_key(2, 0).unwrap_err(),
+            ObfuscationError::DuplicateVersion(2)
+        );
+    }
+
+    #[test]
+    fn test_sqids() {
+        // Vectors of the reference implementation
+        let sqids = Sqids::default();
+        assert_eq!(sqids.encode(&[1, 2, 3]).unwrap(), "86Rf07");
+        assert_eq!(sqids.decode("86Rf07"), vec![1, 2, 3]);
+        assert_eq!(sqids.encode(&[0]).unwrap(), "bM");
+        assert_eq!(
+            Sqids::new(SqidsOptions::new().min_length(62))
+                .unwrap()
+                .encode(&[1, 2, 3])
+                .unwrap(),
+            "86Rf07xd4zBmiJXQG6otHEbew02c3PWsUOLZxADhCpKj7aVFv9I8RquYrNlSTM"
+        );
+        assert_eq!(
+            Sqids::new(SqidsOptions::new().alphabet("0123456789abcdef"))
+                .unwrap()
+                .encode(&[1, 2, 3])
+                .unwrap(),
+            "489158"
+        );
+        assert_eq!(sqids.encode(&[4572721]).unwrap(), "JExTR");
+        assert_eq!(sqids.decode("JExTR"), vec![4572721]);
+        assert_eq!(sqids.decode("aho1e"), vec![4572721]);
+        let unblocked = Sqids::new(SqidsOptions::new().blocklist(Vec::<String>::new())).unwrap();
+        assert_eq!(unblocked.encode(&[4572721]).unwrap(), "aho1e");
+
+        // Round trip of random ids with random options
+        let mut state = 42;
+        let generator = IdGenerator::new(
+            IdGeneratorOptions::new().startup_strategy(StartupStrategy::Immediate),
+        )
+        .unwrap();
+        for _ in 0..200 {
+            let mut alphabet: Vec<char> = sqids::DEFAULT_ALPHABET.chars().collect();
+            let offset = generator::splitmix64(&mut state) as usize % alphabet.len();
+            alphabet.rotate_left(offset);
+            alphabet.truncate(3 + generator::splitmix64(&mut state) as usize % 60);
+            let min_length = generator::splitmix64(&mut state) as u8 % 40;
+            let options = SqidsOptions::new()
+                .alphabet(alphabet.into_iter().collect::<String>())
+                .min_length(min_length);
+            let sqids = Sqids::new(options.clone()).unwrap();
+            let mut ids = generator.next_ids(generator::splitmix64(&mut state) as usize % 4);
+            ids.push((generator::splitmix64(&mut state) >> 1) as i64);
+            ids.push(0);
+            let encoded = sqids.encode(&ids).unwrap();
+            assert!(encoded.len() >= min_length as usize);
+            assert_eq!(sqids.decode(&encoded), ids);
+
+            // Blocking a word of the string changes the string, but not the ids
+            let word = encoded[..4.min(encoded.len())].to_string();
+            let blocked = Sqids::new(options.blocklist([word.clone()])).unwrap();
+            match blocked.encode(&ids) {
+                Ok(reencoded) => {
+                    assert!(!reencoded.to_lowercase().starts_with(&word.to_lowercase()));
+                    assert_eq!(blocked.decode(&reencoded), ids);
+                }
+                Err(e) => assert_eq!(e, SqidsError::MaxAttempts),
+            }
+        }
+
+        assert_eq!(sqids.encode(&[]).unwrap(), "");
+        assert_eq!(sqids.decode(""), Vec::<i64>::new());
+        assert_eq!(sqids.decode("86Rf0#"), Vec::<i64>::new());
+        assert_eq!(sqids.encode(&[-1]), Err(SqidsError::NegativeId(-1)));
+        assert!(matches!(
+            Sqids::new(SqidsOptions::new().alphabet("ab")),
+            Err(SqidsError::InvalidAlphabet(_))
+        ));
+        assert!(matches!(
+            Sqids::new(SqidsOptions::new().alphabet("abca")),
+            Err(SqidsError::InvalidAlphabet(_))
+        ));
+    }
+
+    #[test]
+    fn test_typed_id() {
+        define_id!(User = "usr");
+        define_id!(Order = "ord", index = 0);
+        assert_eq!(User::VEC_INDEX, None);
+        assert_eq!(Order::VEC_INDEX, Some(0));
+
+        let registry: IdRegistry = IdRegistry::new();
+        let options = IdGeneratorOptions::new().startup_strategy(StartupStrategy::Immediate);
+        registry
+            .register("usr".to_string(), options.clone())
+            .unwrap();
+        let user_id = TypedId::<User>::next_in(&registry).unwrap();
+        let displayed = user_id.to_string();
+        assert!(displayed.starts_with("usr_"));
+        assert_eq!(displayed.len(), 15);
+        assert_eq!(displayed.parse::<TypedId<User>>().unwrap(), user_id);
+        assert_eq!(i64::from(user_id), user_id.id());
+        assert!(TypedId::<Order>::next_in(&registry).is_err());
+
+        let generator = IdGenerator::new(options).unwrap();
+        let first = TypedId::<User>::next_from(&generator).unwrap();
+        let second = TypedId::<User>::next_from(&generator).unwrap();
+        assert!(first < second);
+        assert!(first.to_string() < second.to_string());
+        assert!(TypedId::<User>::next().is_ok());
+
+        let order_id = TypedId::<Order>::new(user_id.id()).to_string();
+        assert_eq!(
+            order_id.parse::<TypedId<User>>(),
+            Err(ParseIdError::WrongPrefix(
+                "usr".to_string(),
+                "ord".to_string()
+            ))
+        );
+        assert!(matches!(
+            "usr_abc".parse::<TypedId<User>>(),
+            Err(ParseIdError::Invalid(_))
+        ));
+
+        // Ids beyond i64::MAX are only parsed for the kinds of 64-bit ids
+        define_id!(Event = "evt", width = IdWidth::Bits64);
+        let beyond = Encoding::Base62.encode_fixed(1 << 63);
+        assert!(matches!(
+            format!("usr_{beyond}").parse::<TypedId<User>>(),
+            Err(ParseIdError::Invalid(_))
+        ));
+        let event_id = format!("evt_{beyond}").parse::<TypedId<Event>>().unwrap();
+        assert_eq!(event_id.id(), i64::MIN);
+        assert_eq!(event_id.to_string(), format!("evt_{beyond}"));
+        assert!(matches!(
+            "usr0AbCdEfGhIj".parse::<TypedId<User>>(),
+            Err(ParseIdError::Invalid(_))
+        ));
+        assert!(matches!(
+            "usr_0AbCdEfG-Ij".parse::<TypedId<User>>(),
+            Err(ParseIdError::Encoding(EncodingError::InvalidChar('-', 8)))
+        ));
+    }
+
+    #[test]
+    fn test_checksum() {
+        assert_eq!(
+            Checksum::Damm.append(Encoding::Decimal, "572"),
+            Ok("5724".to_string())
+        );
+        assert_eq!(
+            Checksum::LuhnModN.check_char(Encoding::Decimal, "7992739871"),
+            Ok('3')
+        );
+        assert_eq!(
+            Checksum::Mod37.check_char(Encoding::Crockford32, "10"),
+            Ok('*')
+        );
+        assert_eq!(
+            Checksum::Mod37.validate(Encoding::Crockford32, "1-4u"),
+            Ok(36)
+        );
+        assert_eq!(
+            Checksum::Damm.append(Encoding::Base62, "572"),
+            Err(ChecksumError::Unsupported(Checksum::Damm, Encoding::Base62))
+        );
+        assert_eq!(
+            Checksum::Mod37.validate(Encoding::Crockford32, "10!"),
+            Err(ChecksumError::InvalidCheckChar('!'))
+        );
+
+        let supported = [
+            (Checksum::Damm, Encoding::Decimal),
+            (Checksum::LuhnModN, Encoding::Decimal),
+            (Checksum::LuhnModN, Encoding::Base62),
+            (Checksum::LuhnModN, Encoding::Base36),
+            (Checksum::Mod37, Encoding::Crockford32),
+        ];
+        let mut state = 7;
+        for _ in 0..200 {
+            let id = generator::splitmix64(&mut state) >> 1;
+            for &(checksum, encoding) in &supported {
+                let checked = checksum
+                    .append(encoding, &encoding.encode_fixed(id))
+                    .unwrap();
+                assert_eq!(checksum.validate(encoding, &checked), Ok(id));
+
+                let alphabet = encoding.alphabet();
+                let mut chars = checked.into_bytes();
+                let len = chars.len();
+                let index = generator::splitmix64(&mut state) as usize % len;
+                let original = chars[index];
+
+                // Single-character errors, including the check character
+                for &c in alphabet.iter().filter(|&&c| c != original) {
+                    chars[index] = c;
+                    let typo = String::from_utf8(chars.clone()).unwrap();
+                    assert!(checksum.validate(encoding, &typo).is_err(), "{}", typo);
+                }
+                chars[index] = original;
+
+                // Adjacent transpositions, missed by Luhn mod N only for the first and last digits
+                let index = index.min(len - 2);
+                if chars[index] == chars[index + 1] {
+                    continue;
+                }
+                let pair = [chars[index], chars[index + 1]];
+                let edge = [alphabet[0], alphabet[alphabet.len() - 1]];
+                if checksum == Checksum::LuhnModN && (pair == edge || pair == [edge[1], edge[0]]) {
+                    continue;
+                }
+                chars.swap(index, index + 1);
+                let typo = String::from_utf8(chars).unwrap();
+                assert!(checksum.validate(encoding, &typo).is_err(), "{}", typo);
+            }
+        }
     }
 }
@@ -0,0 +1,156 @@
+//! # Obfuscator
+//!
+//! Opaque external ids for the raw ids stored internally.
+//!
+//! Snowflake ids leak when and by which worker they were generated, and how many were generated in between.
+//! `IdObfuscator` maps the 63-bit ids through a keyed permutation and back, so they can be exposed without
+//! leaking any of it. It is obfuscation rather than encryption: the key can not be recovered from a few ids,
+//! but do not rely on it to protect secrets.
+
+use std::collections::BTreeMap;
+use std::fmt::{self, Debug};
+
+use crate::generator::splitmix64;
+use crate::{Encoding, ObfuscationError};
+
+/// Rounds of the Feistel network
+const ROUNDS: usize = 8;
+
+/// Largest key version, the last character of the base62 alphabet
+const MAX_VERSION: u8 = 61;
+
+/// Reversible keyed permutation of the ids, with key rotation
+///
+/// Ids in \[0, 2^63) are permuted by a balanced Feistel network over 64 bits, walking the cycle until
+/// the result falls back in \[0, 2^63), so every non-negative `i64` maps to exactly one non-negative `i64`.
+///
+/// `obfuscate` prefixes the base62 id of fixed width with the key version as a base62 character,
+/// e.g. `1` followed by 11 characters. To rotate the key, create an obfuscator with the new key and version,
+/// and add the old keys with `with_retired_key` to keep revealing the ids obfuscated before.
+#[derive(Clone)]
+pub struct IdObfuscator {
+    version: u8,
+    keys: BTreeMap<u8, [u64; ROUNDS]>,
+}
+
+impl IdObfuscator {
+    /// Create an obfuscator with the key of the version, in range \[0, 61\]
+    pub fn new(version: u8, key: u64) -> Result<Self, ObfuscationError> {
+        let obfuscator = IdObfuscator {
+            version,
+            keys: BTreeMap::new(),
+        };
+        obfuscator.with_key(version, key)
+    }
+
+    /// Add a key of another version, only used to reveal the ids obfuscated with it
+    pub fn with_retired_key(self, version: u8, key: u64) -> Result<Self, ObfuscationError> {
+        self.with_key(version, key)
+    }
+
+    fn with_key(mut self, version: u8, key: u64) -> Result<Self, ObfuscationError> {
+        if version > MAX_VERSION {
+            return Err(ObfuscationError::InvalidVersion(version));
+        }
+        if self.keys.contains_key(&version) {
+            return Err(ObfuscationError::DuplicateVersion(version));
+        }
+        let mut state = key;
+        let round_keys = [(); ROUNDS].map(|_| splitmix64(&mut state));
+        self.keys.insert(version, round_keys);
+        Ok(self)
+    }
+
+    /// Version of the key ids are obfuscated with
+    pub fn version(&self) -> u8 {
+        self.version
+    }
+
+    /// Permute the id with the current key
+    pub fn permute(&self, id: i64) -> Result<i64, ObfuscationError> {
+        if id < 0 {
+            return Err(ObfuscationError::NegativeId(id));
+        }
+        let round_keys = &self.keys[&self.version];
+        let mut permuted = id as u64;
+        // Cycle walking: permute again until back in [0, 2^63), at the latest when the cycle returns to `id`
+        loop {
+            permuted = feistel(permuted, round_keys);
+            if permuted >> 63 == 0 {
+                return Ok(permuted as i64);
+            }
+        }
+    }
+
+    /// Reverse `permute` with the key of the version
+    pub fn unpermute(&self, version: u8, permuted: i64) -> Result<i64, ObfuscationError> {
+        if permuted < 0 {
+            return Err(ObfuscationError::NegativeId(permuted));
+        }
+        let round_keys = self
+            .keys
+            .get(&version)
+            .ok_or(ObfuscationError::UnknownVersion(version))?;
+        let mut id = permuted as u64;
+        loop {
+            id = feistel_inverse(id, round_keys);
+            if id >> 63 == 0 {
+                return Ok(id as i64);
+            }
+        }
+    }
+
+    /// Obfuscate the id into the key version and the permuted id in base62, 12 characters in total
+    pub fn obfuscate(&self, id: i64) -> Result<String, ObfuscationError> {
+        let permuted = self.permute(id)?;
+        let mut obfuscated = Encoding::Base62.encode(self.version as u64);
+        obfuscated.push_str(&Encoding::Base62.encode_fixed(permuted as u64));
+        Ok(obfuscated)
+    }
+
+    /// Reveal the id obfuscated with any of the keys
+    pub fn reveal(&self, obfuscated: &str) -> Result<i64, ObfuscationError> {
+        if obfuscated.len() != 1 + Encoding::Base62.fixed_width() || !obfuscated.is_ascii() {
+            return Err(ObfuscationError::InvalidId(obfuscated.to_string()));
+        }
+        let (version, permuted) = obfuscated.split_at(1);
+        let version = Encoding::Base62.decode(version)? as u8;
+        let permuted = Encoding::Base62.decode(permuted)?;
+        if permuted >> 63 != 0 {
+            return Err(ObfuscationError::InvalidId(obfuscated.to_string()));
+        }
+        self.unpermute(version, permuted as i64)
+    }
+}
+
+impl Debug for IdObfuscator {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // Never print the keys
+        f.debug_struct("IdObfuscator")
+            .field("version", &self.version)
+            .field("versions", &self.keys.keys().collect::<Vec<_>>())
+            .finish_non_exhaustive()
+    }
+}
+
+/// Round function of the Feistel network
+fn round(half: u32, round_key: u64) -> u32 {
+    let mut state = half as u64 ^ round_key;
+    splitmix64(&mut state) as u32
+}
+
+fn feistel(block: u64, round_keys: &[u64; ROUNDS]) -> u64 {
+    let (mut left, mut right) = ((block >> 32) as u32, block as u32);
+    for &round_key in round_keys {
+        (left, right) = (right, left ^ round(right, round_key));
+    }
+    ((left as u64) << 32) | right as u64
+}
+
+fn feistel_inverse(block: u64, round_keys: &[u64; ROUNDS]) -> u64 {
+    let (mut left, mut right) = ((block >> 32) as u32, block as u32);
+    for &round_key in round_keys.iter().rev() {
+        (left, right) = (right ^ round(left, round_key), left);
+    }
+    ((left as u64) << 32) | right as u64
+}
@@ -2,26 +2,109 @@
 //!
 //! IdGeneratorOptions will provide you a interface for setting generators' options
 
+use chrono::{DateTime, TimeZone, Utc};
+
+use crate::generator::InstanceOptions;
+use crate::{IdLayout, OptionError};
+
+/// How a generator avoids overlapping the ids generated before by others
+///
+/// Applied when a generator is initialized for the first time or switches to another worker id or datacenter id.
+/// A running generator keeps generating after its own ids when its options are set again, without waiting.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StartupStrategy {
+    /// Sleep 500ms when method is 1, to skip the ticks a previous generator (e.g. in a previous process) may have borrowed by drift,
+    /// or wait for the next time tick when method is 2, which never borrows ticks.
+    /// Either way, then wait until the current time passes the time persisted by the `TickStore`
+    Sleep,
+    /// Wait only until the current time passes the last time tick generated by this generator,
+    /// or the one persisted by the `TickStore`; without a tick store a new generator does not wait
+    WaitLastTick,
+    /// Do not wait at all
+    Immediate,
+}
+
+/// Unit of the time ticks in the ids
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TimeUnit {
+    /// 1 millisecond
+    Millisecond,
+    /// 10 milliseconds, as Sonyflake
+    TenMilliseconds,
+    /// 100 milliseconds
+    HundredMilliseconds,
+    /// 1 second
+    Second,
+}
+
+impl TimeUnit {
+    /// Length of the unit in milliseconds
+    pub fn millis(self) -> i64 {
+        match self {
+            TimeUnit::Millisecond => 1,
+            TimeUnit::TenMilliseconds => 10,
+            TimeUnit::HundredMilliseconds => 100,
+            TimeUnit::Second => 1000,
+        }
+    }
+}
+
+/// Bit width of the ids
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IdWidth {
+    /// 63 bits, leaving the sign bit of `i64` 0 so that the ids are positive
+    Bits63,
+    /// All the 64 bits, the time tick takes the sign bit too, so read the ids with `next_u64`
+    Bits64,
+    /// 128 bits of `time_tick (48) | datacenter_id | worker_id (16 in total) | sequence | random`, read with `next_u128`
+    ///
+    /// The sequence takes the highest `seq_bit_len` bits of the lower 64 bits, in range \[2, 31\],
+    /// and the bits below it are random. Custom layouts are not supported.
+    Bits128,
+}
+
+impl IdWidth {
+    /// Number of bits
+    pub fn bit_len(self) -> u8 {
+        match self {
+            IdWidth::Bits63 => 63,
+            IdWidth::Bits64 => 64,
+            IdWidth::Bits128 => 128,
+        }
+    }
+}
+
 /// Options for CoreIdGenerator
 ///
 /// ## Parameters
 ///
-/// - `method`: 1 means snowflake with shift.
+/// - `method`: 1 means snowflake with shift, 2 means classic snowflake which waits for the next millisecond when the sequence is exhausted, default to 1.
 /// - `base_time`: base time of the snowflake algorithm, in milliseconds, can not exceed the current system time.
+/// - `time_unit`: unit of the time ticks, default to `TimeUnit::Millisecond`. Coarser units make the ids last longer and shorter, e.g. `TimeUnit::TenMilliseconds` as Sonyflake. `base_time` should be a multiple of it.
 /// - `worker_id`: should be decided externally, smaller than `2^worker_id_bit_len-1`.
 /// - `worker_id_bit_len`: the bit length of worker_id, default to 8, in range \[1, 15\]. **`worker_id_bit_len + seq_bit_len` should be less than 22**.
+/// - `datacenter_id`: id of the datacenter, smaller than `2^datacenter_id_bit_len-1`, default to 0.
+/// - `datacenter_id_bit_len`: the bit length of datacenter_id placed between the timestamp and worker_id, default to 0 meaning no datacenter field, in range \[0, 19\]. **`datacenter_id_bit_len + worker_id_bit_len + seq_bit_len` should not exceed 22**.
 /// - `seq_bit_len`: the bit length of sequence, default to 8, in range \[3, 21\].
 /// - `max_seq_num`: set the range of \[min_seq_num, 2^seq_bit_len-1\], default to 0 meaning `2^seq_bit_len-1`.
 /// - `min_seq_num`: default to 5, range \[5, max_seq_num\], reserved for manually value and time turned back.
 /// - `top_over_cost_count`: max shift count(included), default to 2000, recommended range is [500, 20000] (associated with computing ability).
+/// - `min_horizon_years`: reject the options if the last representable time is within this number of years from now, default to 0 meaning no check.
+/// - `startup_strategy`: how to avoid overlapping the ids generated before initializing or setting options, default to `StartupStrategy::Sleep`, see `StartupStrategy`.
+/// - `id_width`: bit width of the ids, default to `IdWidth::Bits63` meaning positive `i64` ids. `IdWidth::Bits64` lets the time tick take the sign bit, for ids generated with `next_u64`, and `IdWidth::Bits128` composes 128-bit ids with wider node and sequence fields for `next_u128`, see `IdWidth`.
+/// - `layout`: custom order and bit lengths of the fields of the ids, taking precedence over the bit lengths above, default to none meaning `time_tick | datacenter_id | worker_id | sequence`, see `IdLayout`.
 #[derive(Debug, Clone, PartialEq, Default)]
 pub struct IdGeneratorOptions {
-    /// Snowflake 1 for shift
+    /// Snowflake 1 for shift, 2 for classic (never ahead of the current time)
     pub method: Option<u8>,
 
     /// base time (in milliseconds), can not exceed the current system time
     pub base_time: Option<i64>,
 
+    /// unit of the time ticks, `base_time` should be a multiple of it
+    /// default to `TimeUnit::Millisecond`
+    pub time_unit: Option<TimeUnit>,
+
     /// should be decided externally, smaller than `2^worker_id_bit_len-1`
     pub worker_id: Option<u32>,
 
@@ -29,6 +112,13 @@ pub struct IdGeneratorOptions {
     /// default to 8, in range \[1, 15\]
     pub worker_id_bit_len: Option<u8>,
 
+    /// id of the datacenter, smaller than `2^datacenter_id_bit_len-1`
+    pub datacenter_id: Option<u32>,
+
+    /// `datacenter_id_bit_len + worker_id_bit_len + seq_bit_len` should not exceed 22
+    /// default to 0 meaning no datacenter field
+    pub datacenter_id_bit_len: Option<u8>,
+
     /// default to 8, in range \[3, 21\]
     pub seq_bit_len: Option<u8>,
 
@@ -40,6 +130,22 @@ pub struct IdGeneratorOptions {
 
     /// max shift count(included), default to 2000, recommended range is [500, 20000] (associated with computing ability)
     pub top_over_cost_count: Option<u32>,
+
+    /// reject the options if the last representable time is within this number of years from now
+    /// default to 0 meaning no check
+    pub min_horizon_years: Option<u32>,
+
+    /// how to avoid overlapping the ids generated before initializing or setting options
+    /// default to `StartupStrategy::Sleep`
+    pub startup_strategy: Option<StartupStrategy>,
+
+    /// bit width of the ids, see `IdWidth`
+    /// default to `IdWidth::Bits63` meaning positive `i64` ids
+    pub id_width: Option<IdWidth>,
+
+    /// custom order and bit lengths of the fields of the ids, see `IdLayout`
+    /// default to none meaning `time_tick | datacenter_id | worker_id | sequence`
+    pub layout: Option<IdLayout>,
 }
 
 impl IdGeneratorOptions {
@@ -47,12 +153,19 @@ impl IdGeneratorOptions {
         IdGeneratorOptions {
             method: None,
             base_time: None,
+            time_unit: None,
             worker_id: None,
             worker_id_bit_len: None,
+            datacenter_id: None,
+            datacenter_id_bit_len: None,
             seq_bit_len: None,
             max_seq_num: None,
             min_seq_num: None,
             top_over_cost_count: None,
+            min_horizon_years: None,
+            startup_strategy: None,
+            id_width: None,
+            layout: None,
         }
     }
 
@@ -66,6 +179,11 @@ impl IdGeneratorOptions {
         self
     }
 
+    pub fn time_unit(mut self, time_unit: TimeUnit) -> Self {
+        self.time_unit = Some(time_unit);
+        self
+    }
+
     pub fn worker_id(mut self, worker_id: u32) -> Self {
         self.worker_id = Some(worker_id);
         self
@@ -76,6 +194,16 @@ impl IdGeneratorOptions {
         self
     }
 
+    pub fn datacenter_id(mut self, datacenter_id: u32) -> Self {
+        self.datacenter_id = Some(datacenter_id);
+        self
+    }
+
+    pub fn datacenter_id_bit_len(mut self, datacenter_id_bit_len: u8) -> Self {
+        self.datacenter_id_bit_len = Some(datacenter_id_bit_len);
+        self
+    }
+
     pub fn seq_bit_len(mut self, seq_bit_len: u8) -> Self {
         self.seq_bit_len = Some(seq_bit_len);
         self
@@ -95,4 +223,61 @@ impl IdGeneratorOptions {
         self.top_over_cost_count = Some(top_over_cost_count);
         self
     }
+
+    pub fn min_horizon_years(mut self, min_horizon_years: u32) -> Self {
+        self.min_horizon_years = Some(min_horizon_years);
+        self
+    }
+
+    pub fn startup_strategy(mut self, startup_strategy: StartupStrategy) -> Self {
+        self.startup_strategy = Some(startup_strategy);
+        self
+    }
+
+    pub fn id_width(mut self, id_width: IdWidth) -> Self {
+        self.id_width = Some(id_width);
+        self
+    }
+
+    pub fn layout(mut self, layout: IdLayout) -> Self {
+        self.layout = Some(layout);
+        self
+    }
+
+    /// How long and how fast ids can be generated with these options
+    ///
+    /// Options not set will be given the default value. `min_horizon_years` is not checked here.
+    pub fn horizon(&self) -> Result<Horizon, OptionError> {
+        let now_millis = Utc::now().timestamp_millis();
+        let mut options: InstanceOptions = self.clone().into();
+        options.min_horizon_years = 0;
+        options.check(now_millis)?;
+        Ok(options.horizon(now_millis))
+    }
+}
+
+/// How long and how fast a generator can generate ids
+#[derive(Debug, Clone, PartialEq)]
+pub struct Horizon {
+    /// Last representable UTC timestamp, in milliseconds
+    pub last_timestamp: i64,
+    /// Years left from now until the last representable timestamp
+    pub remaining_years: f64,
+    /// Max ids generated in one time tick, excluding the reserved sequence numbers
+    pub ids_per_tick: u64,
+    /// Max ids generated in one second, sustained
+    ///
+    /// With method 1, a burst may exceed it once by `max_burst_ids`, after which the generator waits
+    /// for the clock to catch up.
+    pub ids_per_second: u64,
+    /// Max ids generated beyond the sustained rate by borrowing up to `top_over_cost_count` ticks ahead by drift,
+    /// 0 with method 2 which never borrows ticks
+    pub max_burst_ids: u64,
+}
+
+impl Horizon {
+    /// The last representable UTC timestamp as a `DateTime`, `None` if it is out of range
+    pub fn last_datetime(&self) -> Option<DateTime<Utc>> {
+        Utc.timestamp_millis_opt(self.last_timestamp).single()
+    }
 }
@@ -0,0 +1,169 @@
+//! # Registry
+//!
+//! A registry of generators addressed by name (or any other key type),
+//! so different business entities (e.g. orders, users, payments) can each have their own generator.
+//!
+//! See `IdVecInstance` for generators addressed by index.
+
+use parking_lot::RwLock;
+use std::borrow::Borrow;
+use std::collections::HashMap;
+use std::fmt::Debug;
+use std::hash::Hash;
+
+use crate::IdBlock;
+use crate::IdGenerator;
+use crate::IdGeneratorOptions;
+use crate::{GenerateError, OptionError};
+
+/// Registry of generators addressed by key
+pub struct IdRegistry<K = String> {
+    generators: RwLock<HashMap<K, IdGenerator>>,
+}
+
+impl<K> Default for IdRegistry<K> {
+    fn default() -> Self {
+        IdRegistry {
+            generators: RwLock::new(HashMap::new()),
+        }
+    }
+}
+
+impl<K: Hash + Eq + Clone + Debug> IdRegistry<K> {
+    pub fn new() -> Self {
+        IdRegistry::default()
+    }
+
+    /// Register a new generator with the given options under the key
+    ///
+    /// The key should not have been registered yet, use `set_options` to change the options of a registered generator.
+    pub fn register(&self, key: K, options: IdGeneratorOptions) -> Result<(), OptionError> {
+        if self.generators.read().contains_key(&key) {
+            return Err(OptionError::DuplicateKey(format!("{key:?}")));
+        }
+        let generator = IdGenerator::new(options)?;
+        let mut generators = self.generators.write();
+        if generators.contains_key(&key) {
+            return Err(OptionError::DuplicateKey(format!("{key:?}")));
+        }
+        generators.insert(key, generator);
+        Ok(())
+    }
+
+    /// Drop the generator registered under the key
+    pub fn unregister<Q>(&self, key: &Q) -> Result<(), OptionError>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + Debug + ?Sized,
+    {
+        match self.generators.write().remove(key) {
+            Some(_) => Ok(()),
+            None => Err(OptionError::KeyNotFound(format!("{key:?}"))),
+        }
+    }
+
+    /// Set options of the generator registered under the key
+    pub fn set_options<Q>(&self, key: &Q, options: IdGeneratorOptions) -> Result<(), OptionError>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + Debug + ?Sized,
+    {
+        let generator = self
+            .generator(key)
+            .ok_or_else(|| OptionError::KeyNotFound(format!("{key:?}")))?;
+        generator.set_options(options)
+    }
+
+    /// Get options of the generator registered under the key
+    pub fn get_options<Q>(&self, key: &Q) -> Result<IdGeneratorOptions, OptionError>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + Debug + ?Sized,
+    {
+        let generator = self
+            .generator(key)
+            .ok_or_else(|| OptionError::KeyNotFound(format!("{key:?}")))?;
+        Ok(generator.get_options())
+    }
+
+    /// Get a unique id from the generator registered under the key
+    pub fn next_id<Q>(&self, key: &Q) -> Result<i64, GenerateError>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + Debug + ?Sized,
+    {
+        let generator = self
+            .generator(key)
+            .ok_or_else(|| GenerateError::KeyNotFound(format!("{key:?}")))?;
+        generator.try_next_id()
+    }
+
+    /// Get `n` unique ids at once from the generator registered under the key
+    pub fn next_ids<Q>(&self, key: &Q, n: usize) -> Result<Vec<i64>, GenerateError>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + Debug + ?Sized,
+    {
+        let generator = self
+            .generator(key)
+            .ok_or_else(|| GenerateError::KeyNotFound(format!("{key:?}")))?;
+        Ok(generator.next_ids(n))
+    }
+
+    /// Reserve a block of `count` unique ids in advance from the generator registered under the key
+    pub fn lease_block<Q>(&self, key: &Q, count: usize) -> Result<IdBlock, GenerateError>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + Debug + ?Sized,
+    {
+        let generator = self
+            .generator(key)
+            .ok_or_else(|| GenerateError::KeyNotFound(format!("{key:?}")))?;
+        Ok(generator.lease_block(count))
+    }
+
+    pub fn contains<Q>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.generators.read().contains_key(key)
+    }
+
+    pub fn len(&self) -> usize {
+        self.generators.read().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.generators.read().is_empty()
+    }
+
+    /// Keys of all the registered generators
+    pub fn keys(&self) -> Vec<K> {
+        self.generators.read().keys().cloned().collect()
+    }
+
+    /// Iterate over the keys and options of all the registered generators, as a snapshot
+    pub fn iter(&self) -> std::vec::IntoIter<(K, IdGeneratorOptions)> {
+        let generators: Vec<_> = self
+            .generators
+            .read()
+            .iter()
+            .map(|(key, generator)| (key.clone(), generator.clone()))
+            .collect();
+        generators
+            .into_iter()
+            .map(|(key, generator)| (key, generator.get_options()))
+            .collect::<Vec<_>>()
+            .into_iter()
+    }
+
+    /// Get a handle of the generator registered under the key
+    pub fn generator<Q>(&self, key: &Q) -> Option<IdGenerator>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.generators.read().get(key).cloned()
+    }
+}
@@ -0,0 +1,832 @@
+//! # Sqids
+//!
+//! Short strings of one or more ids, compatible with [Sqids](https://sqids.org), the successor of Hashids.
+//!
+//! Strings encoded here are decoded by the Sqids libraries of other languages and vice versa,
+//! given the same alphabet, minimum length and blocklist.
+//! The reference blocklist is bundled as `DEFAULT_BLOCKLIST` and used by default.
+
+use std::collections::HashSet;
+
+use crate::SqidsError;
+
+/// Alphabet of the reference implementation
+pub const DEFAULT_ALPHABET: &str = "abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ0123456789";
+
+/// Options for Sqids
+///
+/// ## Parameters
+///
+/// - `alphabet`: unique ASCII characters of the strings, at least 3, default to `DEFAULT_ALPHABET`.
+/// - `min_length`: pad the strings to at least this length, default to 0.
+/// - `blocklist`: words the strings should not contain, case-insensitively, default to `DEFAULT_BLOCKLIST`.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct SqidsOptions {
+    /// unique ASCII characters of the strings, at least 3
+    /// default to `DEFAULT_ALPHABET`
+    pub alphabet: Option<String>,
+
+    /// pad the strings to at least this length
+    /// default to 0
+    pub min_length: Option<u8>,
+
+    /// words the strings should not contain, case-insensitively
+    /// default to `DEFAULT_BLOCKLIST`, set it empty to block no word
+    pub blocklist: Option<Vec<String>>,
+}
+
+impl SqidsOptions {
+    pub fn new() -> Self {
+        SqidsOptions::default()
+    }
+
+    pub fn alphabet<S: Into<String>>(mut self, alphabet: S) -> Self {
+        self.alphabet = Some(alphabet.into());
+        self
+    }
+
+    pub fn min_length(mut self, min_length: u8) -> Self {
+        self.min_length = Some(min_length);
+        self
+    }
+
+    pub fn blocklist<I: IntoIterator<Item = S>, S: Into<String>>(mut self, blocklist: I) -> Self {
+        self.blocklist = Some(blocklist.into_iter().map(Into::into).collect());
+        self
+    }
+}
+
+/// Encoder and decoder of Sqids strings
+#[derive(Debug, Clone)]
+pub struct Sqids {
+    /// Alphabet shuffled once, as the reference implementation
+    alphabet: Vec<u8>,
+    min_length: usize,
+    /// Lowercase words of at least 3 characters, all in the alphabet
+    blocklist: HashSet<String>,
+}
+
+impl Default for Sqids {
+    fn default() -> Self {
+        Sqids::new(SqidsOptions::new()).unwrap()
+    }
+}
+
+impl Sqids {
+    /// Create an encoder with the given options
+    ///
+    /// Options not set will be given the default value.
+    pub fn new(options: SqidsOptions) -> Result<Self, SqidsError> {
+        let alphabet = options
+            .alphabet
+            .unwrap_or_else(|| DEFAULT_ALPHABET.to_string());
+        if !alphabet.is_ascii() {
+            return Err(SqidsError::InvalidAlphabet(
+                "should only have ASCII characters".to_string(),
+            ));
+        }
+        if alphabet.len() < 3 {
+            return Err(SqidsError::InvalidAlphabet(
+                "should have at least 3 characters".to_string(),
+            ));
+        }
+        if alphabet.bytes().collect::<HashSet<_>>().len() != alphabet.len() {
+            return Err(SqidsError::InvalidAlphabet(
+                "should have unique characters".to_string(),
+            ));
+        }
+
+        // Words which can never appear in the strings are dropped
+        let lowercase_alphabet = alphabet.to_ascii_lowercase();
+        let blocklist = options
+            .blocklist
+            .unwrap_or_else(|| {
+                DEFAULT_BLOCKLIST
+                    .iter()
+                    .map(|word| word.to_string())
+                    .collect()
+            })
+            .into_iter()
+            .map(|word| word.to_ascii_lowercase())
+            .filter(|word| word.len() >= 3 && word.chars().all(|c| lowercase_alphabet.contains(c)))
+            .collect();
+
+        let mut alphabet = alphabet.into_bytes();
+        shuffle(&mut alphabet);
+        Ok(Sqids {
+            alphabet,
+            min_length: options.min_length.unwrap_or(0) as usize,
+            blocklist,
+        })
+    }
+
+    /// Encode the ids into one string, empty if there is no id
+    pub fn encode(&self, ids: &[i64]) -> Result<String, SqidsError> {
+        if let Some(&id) = ids.iter().find(|&&id| id < 0) {
+            return Err(SqidsError::NegativeId(id));
+        }
+        if ids.is_empty() {
+            return Ok(String::new());
+        }
+        let ids: Vec<u64> = ids.iter().map(|&id| id as u64).collect();
+        self.encode_numbers(&ids, 0)
+    }
+
+    /// Decode the ids of the string, empty if it is not encoded with the same options
+    ///
+    /// As the reference implementation, the ids decoded before an invalid part are returned.
+    /// Strings of numbers beyond `i64` from other libraries are decoded as empty.
+    pub fn decode(&self, encoded: &str) -> Vec<i64> {
+        let mut ids = Vec::new();
+        let encoded = encoded.as_bytes();
+        let prefix = match encoded.first() {
+            Some(prefix) => prefix,
+            None => return ids,
+        };
+        if !encoded.iter().all(|c| self.alphabet.contains(c)) {
+            return ids;
+        }
+        let offset = self.alphabet.iter().position(|c| c == prefix).unwrap();
+        let mut alphabet = self.alphabet.clone();
+        alphabet.rotate_left(offset);
+        alphabet.reverse();
+
+        let mut rest = &encoded[1..];
+        while !rest.is_empty() {
+            let separator = alphabet[0];
+            let (chunk, remaining) = match rest.iter().position(|&c| c == separator) {
+                Some(index) => (&rest[..index], Some(&rest[index + 1..])),
+                None => (rest, None),
+            };
+            if chunk.is_empty() {
+                return ids;
+            }
+            match to_number(chunk, &alphabet[1..]) {
+                Some(id) if id <= i64::MAX as u64 => ids.push(id as i64),
+                _ => return Vec::new(),
+            }
+            if remaining.is_some() {
+                shuffle(&mut alphabet);
+            }
+            rest = remaining.unwrap_or_default();
+        }
+        ids
+    }
+
+    fn encode_numbers(&self, numbers: &[u64], increment: usize) -> Result<String, SqidsError> {
+        let len = self.alphabet.len();
+        if increment > len {
+            return Err(SqidsError::MaxAttempts);
+        }
+        let offset = numbers
+            .iter()
+            .enumerate()
+            .fold(numbers.len(), |offset, (i, &number)| {
+                self.alphabet[(number % len as u64) as usize] as usize + i + offset
+            });
+        let mut alphabet = self.alphabet.clone();
+        alphabet.rotate_left((offset % len + increment) % len);
+        let prefix = alphabet[0];
+        alphabet.reverse();
+
+        let mut encoded = vec![prefix];
+        for (i, &number) in numbers.iter().enumerate() {
+            encoded.extend(to_id(number, &alphabet[1..]));
+            if i < numbers.len() - 1 {
+                encoded.push(alphabet[0]);
+                shuffle(&mut alphabet);
+            }
+        }
+        if self.min_length > encoded.len() {
+            encoded.push(alphabet[0]);
+            while self.min_length > encoded.len() {
+                shuffle(&mut alphabet);
+                let padding = (self.min_length - encoded.len()).min(len);
+                encoded.extend(&alphabet[..padding]);
+            }
+        }
+
+        let encoded = String::from_utf8(encoded).unwrap();
+        if self.is_blocked(&encoded) {
+            return self.encode_numbers(numbers, increment + 1);
+        }
+        Ok(encoded)
+    }
+
+    fn is_blocked(&self, encoded: &str) -> bool {
+        let encoded = encoded.to_ascii_lowercase();
+        self.blocklist.iter().any(|word| {
+            if word.len() > encoded.len() {
+                false
+            } else if encoded.len() <= 3 || word.len() <= 3 {
+                encoded == *word
+            } else if word.bytes().any(|c| c.is_ascii_digit()) {
+                // Words with leetspeak digits are only blocked at the ends
+                encoded.starts_with(word.as_str()) || encoded.ends_with(word.as_str())
+            } else {
+                encoded.contains(word.as_str())
+            }
+        })
+    }
+}
+
+/// Shuffle the alphabet deterministically, as the reference implementation
+fn shuffle(alphabet: &mut [u8]) {
+    let len = alphabet.len();
+    let (mut i, mut j) = (0, len - 1);
+    while j > 0 {
+        let r = (i * j + alphabet[i] as usize + alphabet[j] as usize) % len;
+        alphabet.swap(i, r);
+        i += 1;
+        j -= 1;
+    }
+}
+
+fn to_id(number: u64, alphabet: &[u8]) -> Vec<u8> {
+    let len = alphabet.len() as u64;
+    let mut number = number;
+    let mut id = Vec::new();
+    loop {
+        id.push(alphabet[(number % len) as usize]);
+        number /= len;
+        if number == 0 {
+            break;
+        }
+    }
+    id.reverse();
+    id
+}
+
+/// Number of the id in the alphabet, `None` if it overflows
+fn to_number(id: &[u8], alphabet: &[u8]) -> Option<u64> {
+    id.iter().try_fold(0_u64, |number, c| {
+        let digit = alphabet.iter().position(|a| a == c)? as u64;
+        number
+            .checked_mul(alphabet.len() as u64)?
+            .checked_add(digit)
+    })
+}
+
+/// Blocklist of the reference implementation, from [sqids-blocklist](https://github.com/sqids/sqids-blocklist) (MIT)
+pub const DEFAULT_BLOCKLIST: &[&str] = &[
+    "0rgasm",
+    "1d10t",
+    "1d1ot",
+    "1di0t",
+    "1diot",
+    "1eccacu10",
+    "1eccacu1o",
+    "1eccacul0",
+    "1eccaculo",
+    "1mbec11e",
+    "1mbec1le",
+    "1mbeci1e",
+    "1mbecile",
+    "a11upat0",
+    "a11upato",
+    "a1lupat0",
+    "a1lupato",
+    "aand",
+    "ah01e",
+    "ah0le",
+    "aho1e",
+    "ahole",
+    "al1upat0",
+    "al1upato",
+    "allupat0",
+    "allupato",
+    "ana1",
+    "ana1e",
+    "anal",
+    "anale",
+    "anus",
+    "arrapat0",
+    "arrapato",
+    "arsch",
+    "arse",
+    "ass",
+    "b00b",
+    "b00be",
+    "b01ata",
+    "b0ceta",
+    "b0iata",
+    "b0ob",
+    "b0obe",
+    "b0sta",
+    "b1tch",
+    "b1te",
+    "b1tte",
+    "ba1atkar",
+    "balatkar",
+    "bastard0",
+    "bastardo",
+    "batt0na",
+    "battona",
+    "bitch",
+    "bite",
+    "bitte",
+    "bo0b",
+    "bo0be",
+    "bo1ata",
+    "boceta",
+    "boiata",
+    "boob",
+    "boobe",
+    "bosta",
+    "bran1age",
+    "bran1er",
+    "bran1ette",
+    "bran1eur",
+    "bran1euse",
+    "branlage",
+    "branler",
+    "branlette",
+    "branleur",
+    "branleuse",
+    "c0ck",
+    "c0g110ne",
+    "c0g11one",
+    "c0g1i0ne",
+    "c0g1ione",
+    "c0gl10ne",
+    "c0gl1one",
+    "c0gli0ne",
+    "c0glione",
+    "c0na",
+    "c0nnard",
+    "c0nnasse",
+    "c0nne",
+    "c0u111es",
+    "c0u11les",
+    "c0u1l1es",
+    "c0u1lles",
+    "c0ui11es",
+    "c0ui1les",
+    "c0uil1es",
+    "c0uilles",
+    "c11t",
+    "c11t0",
+    "c11to",
+    "c1it",
+    "c1it0",
+    "c1ito",
+    "cabr0n",
+    "cabra0",
+    "cabrao",
+    "cabron",
+    "caca",
+    "cacca",
+    "cacete",
+    "cagante",
+    "cagar",
+    "cagare",
+    "cagna",
+    "cara1h0",
+    "cara1ho",
+    "caracu10",
+    "caracu1o",
+    "caracul0",
+    "caraculo",
+    "caralh0",
+    "caralho",
+    "cazz0",
+    "cazz1mma",
+    "cazzata",
+    "cazzimma",
+    "cazzo",
+    "ch00t1a",
+    "ch00t1ya",
+    "ch00tia",
+    "ch00tiya",
+    "ch0d",
+    "ch0ot1a",
+    "ch0ot1ya",
+    "ch0otia",
+    "ch0otiya",
+    "ch1asse",
+    "ch1avata",
+    "ch1er",
+    "ch1ng0",
+    "ch1ngadaz0s",
+    "ch1ngadazos",
+    "ch1ngader1ta",
+    "ch1ngaderita",
+    "ch1ngar",
+    "ch1ngo",
+    "ch1ngues",
+    "ch1nk",
+    "chatte",
+    "chiasse",
+    "chiavata",
+    "chier",
+    "ching0",
+    "chingadaz0s",
+    "chingadazos",
+    "chingader1ta",
+    "chingaderita",
+    "chingar",
+    "chingo",
+    "chingues",
+    "chink",
+    "cho0t1a",
+    "cho0t1ya",
+    "cho0tia",
+    "cho0tiya",
+    "chod",
+    "choot1a",
+    "choot1ya",
+    "chootia",
+    "chootiya",
+    "cl1t",
+    "cl1t0",
+    "cl1to",
+    "clit",
+    "clit0",
+    "clito",
+    "cock",
+    "cog110ne",
+    "cog11one",
+    "cog1i0ne",
+    "cog1ione",
+    "cogl10ne",
+    "cogl1one",
+    "cogli0ne",
+    "coglione",
+    "cona",
+    "connard",
+    "connasse",
+    "conne",
+    "cou111es",
+    "cou11les",
+    "cou1l1es",
+    "cou1lles",
+    "coui11es",
+    "coui1les",
+    "couil1es",
+    "couilles",
+    "cracker",
+    "crap",
+    "cu10",
+    "cu1att0ne",
+    "cu1attone",
+    "cu1er0",
+    "cu1ero",
+    "cu1o",
+    "cul0",
+    "culatt0ne",
+    "culattone",
+    "culer0",
+    "culero",
+    "culo",
+    "cum",
+    "cunt",
+    "d11d0",
+    "d11do",
+    "d1ck",
+    "d1ld0",
+    "d1ldo",
+    "damn",
+    "de1ch",
+    "deich",
+    "depp",
+    "di1d0",
+    "di1do",
+    "dick",
+    "dild0",
+    "dildo",
+    "dyke",
+    "encu1e",
+    "encule",
+    "enema",
+    "enf01re",
+    "enf0ire",
+    "enfo1re",
+    "enfoire",
+    "estup1d0",
+    "estup1do",
+    "estupid0",
+    "estupido",
+    "etr0n",
+    "etron",
+    "f0da",
+    "f0der",
+    "f0ttere",
+    "f0tters1",
+    "f0ttersi",
+    "f0tze",
+    "f0utre",
+    "f1ca",
+    "f1cker",
+    "f1ga",
+    "fag",
+    "fica",
+    "ficker",
+    "figa",
+    "foda",
+    "foder",
+    "fottere",
+    "fotters1",
+    "fottersi",
+    "fotze",
+    "foutre",
+    "fr0c10",
+    "fr0c1o",
+    "fr0ci0",
+    "fr0cio",
+    "fr0sc10",
+    "fr0sc1o",
+    "fr0sci0",
+    "fr0scio",
+    "froc10",
+    "froc1o",
+    "froci0",
+    "frocio",
+    "frosc10",
+    "frosc1o",
+    "frosci0",
+    "froscio",
+    "fuck",
+    "g00",
+    "g0o",
+    "g0u1ne",
+    "g0uine",
+    "gandu",
+    "go0",
+    "goo",
+    "gou1ne",
+    "gouine",
+    "gr0gnasse",
+    "grognasse",
+    "haram1",
+    "harami",
+    "haramzade",
+    "hund1n",
+    "hundin",
+    "id10t",
+    "id1ot",
+    "idi0t",
+    "idiot",
+    "imbec11e",
+    "imbec1le",
+    "imbeci1e",
+    "imbecile",
+    "j1zz",
+    "jerk",
+    "jizz",
+    "k1ke",
+    "kam1ne",
+    "kamine",
+    "kike",
+    "leccacu10",
+    "leccacu1o",
+    "leccacul0",
+    "leccaculo",
+    "m1erda",
+    "m1gn0tta",
+    "m1gnotta",
+    "m1nch1a",
+    "m1nchia",
+    "m1st",
+    "mam0n",
+    "mamahuev0",
+    "mamahuevo",
+    "mamon",
+    "masturbat10n",
+    "masturbat1on",
+    "masturbate",
+    "masturbati0n",
+    "masturbation",
+    "merd0s0",
+    "merd0so",
+    "merda",
+    "merde",
+    "merdos0",
+    "merdoso",
+    "mierda",
+    "mign0tta",
+    "mignotta",
+    "minch1a",
+    "minchia",
+    "mist",
+    "musch1",
+    "muschi",
+    "n1gger",
+    "neger",
+    "negr0",
+    "negre",
+    "negro",
+    "nerch1a",
+    "nerchia",
+    "nigger",
+    "orgasm",
+    "p00p",
+    "p011a",
+    "p01la",
+    "p0l1a",
+    "p0lla",
+    "p0mp1n0",
+    "p0mp1no",
+    "p0mpin0",
+    "p0mpino",
+    "p0op",
+    "p0rca",
+    "p0rn",
+    "p0rra",
+    "p0uff1asse",
+    "p0uffiasse",
+    "p1p1",
+    "p1pi",
+    "p1r1a",
+    "p1rla",
+    "p1sc10",
+    "p1sc1o",
+    "p1sci0",
+    "p1scio",
+    "p1sser",
+    "pa11e",
+    "pa1le",
+    "pal1e",
+    "palle",
+    "pane1e1r0",
+    "pane1e1ro",
+    "pane1eir0",
+    "pane1eiro",
+    "panele1r0",
+    "panele1ro",
+    "paneleir0",
+    "paneleiro",
+    "patakha",
+    "pec0r1na",
+    "pec0rina",
+    "pecor1na",
+    "pecorina",
+    "pen1s",
+    "pendej0",
+    "pendejo",
+    "penis",
+    "pip1",
+    "pipi",
+    "pir1a",
+    "pirla",
+    "pisc10",
+    "pisc1o",
+    "pisci0",
+    "piscio",
+    "pisser",
+    "po0p",
+    "po11a",
+    "po1la",
+    "pol1a",
+    "polla",
+    "pomp1n0",
+    "pomp1no",
+    "pompin0",
+    "pompino",
+    "poop",
+    "porca",
+    "porn",
+    "porra",
+    "pouff1asse",
+    "pouffiasse",
+    "pr1ck",
+    "prick",
+    "pussy",
+    "put1za",
+    "puta",
+    "puta1n",
+    "putain",
+    "pute",
+    "putiza",
+    "puttana",
+    "queca",
+    "r0mp1ba11e",
+    "r0mp1ba1le",
+    "r0mp1bal1e",
+    "r0mp1balle",
+    "r0mpiba11e",
+    "r0mpiba1le",
+    "r0mpibal1e",
+    "r0mpiballe",
+    "rand1",
+    "randi",
+    "rape",
+    "recch10ne",
+    "recch1one",
+    "recchi0ne",
+    "recchione",
+    "retard",
+    "romp1ba11e",
+    "romp1ba1le",
+    "romp1bal1e",
+    "romp1balle",
+    "rompiba11e",
+    "rompiba1le",
+    "rompibal1e",
+    "rompiballe",
+    "ruff1an0",
+    "ruff1ano",
+    "ruffian0",
+    "ruffiano",
+    "s1ut",
+    "sa10pe",
+    "sa1aud",
+    "sa1ope",
+    "sacanagem",
+    "sal0pe",
+    "salaud",
+    "salope",
+    "saugnapf",
+    "sb0rr0ne",
+    "sb0rra",
+    "sb0rrone",
+    "sbattere",
+    "sbatters1",
+    "sbattersi",
+    "sborr0ne",
+    "sborra",
+    "sborrone",
+    "sc0pare",
+    "sc0pata",
+    "sch1ampe",
+    "sche1se",
+    "sche1sse",
+    "scheise",
+    "scheisse",
+    "schlampe",
+    "schwachs1nn1g",
+    "schwachs1nnig",
+    "schwachsinn1g",
+    "schwachsinnig",
+    "schwanz",
+    "scopare",
+    "scopata",
+    "sexy",
+    "sh1t",
+    "shit",
+    "slut",
+    "sp0mp1nare",
+    "sp0mpinare",
+    "spomp1nare",
+    "spompinare",
+    "str0nz0",
+    "str0nza",
+    "str0nzo",
+    "stronz0",
+    "stronza",
+    "stronzo",
+    "stup1d",
+    "stupid",
+    "succh1am1",
+    "succh1ami",
+    "succhiam1",
+    "succhiami",
+    "sucker",
+    "t0pa",
+    "tapette",
+    "test1c1e",
+    "test1cle",
+    "testic1e",
+    "testicle",
+    "tette",
+    "topa",
+    "tr01a",
+    "tr0ia",
+    "tr0mbare",
+    "tr1ng1er",
+    "tr1ngler",
+    "tring1er",
+    "tringler",
+    "tro1a",
+    "troia",
+    "trombare",
+    "turd",
+    "twat",
+    "vaffancu10",
+    "vaffancu1o",
+    "vaffancul0",
+    "vaffanculo",
+    "vag1na",
+    "vagina",
+    "verdammt",
+    "verga",
+    "w1chsen",
+    "wank",
+    "wichsen",
+    "x0ch0ta",
+    "x0chota",
+    "xana",
+    "xoch0ta",
+    "xochota",
+    "z0cc01a",
+    "z0cc0la",
+    "z0cco1a",
+    "z0ccola",
+    "z1z1",
+    "z1zi",
+    "ziz1",
+    "zizi",
+    "zocc01a",
+    "zocc0la",
+    "zocco1a",
+    "zoccola",
+];
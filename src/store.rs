@@ -0,0 +1,75 @@
+//! # Store
+//!
+//! Persist the last time a generator has generated ids up to, so that after a restart
+//! (even with the clock set backwards) it resumes strictly after the ids generated before.
+//!
+//! The generator does not save on every id. It saves a high-water mark ahead of the time it generates ids at,
+//! and saves a new one only when it reaches the mark.
+
+use std::fmt::Debug;
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::path::PathBuf;
+
+/// A storage of the last time (high-water mark) a generator has generated ids up to
+pub trait TickStore: Debug + Send + Sync {
+    /// Load the persisted time, in milliseconds since the Unix epoch, `None` if nothing has been persisted
+    fn load(&self) -> io::Result<Option<i64>>;
+
+    /// Persist the time, in milliseconds since the Unix epoch
+    fn save(&self, timestamp: i64) -> io::Result<()>;
+}
+
+/// Store the time in a file
+///
+/// The file is written atomically: the time is written and synced to a temporary file next to it,
+/// which is then renamed to the file.
+#[derive(Debug, Clone)]
+pub struct FileTickStore {
+    path: PathBuf,
+}
+
+impl FileTickStore {
+    pub fn new<P: Into<PathBuf>>(path: P) -> Self {
+        FileTickStore { path: path.into() }
+    }
+
+    fn temp_path(&self) -> PathBuf {
+        let mut file_name = self.path.file_name().unwrap_or_default().to_os_string();
+        file_name.push(".tmp");
+        self.path.with_file_name(file_name)
+    }
+}
+
+impl TickStore for FileTickStore {
+    fn load(&self) -> io::Result<Option<i64>> {
+        match fs::read_to_string(&self.path) {
+            Ok(content) => content
+                .trim()
+                .parse()
+                .map(Some)
+                .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e)),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    fn save(&self, timestamp: i64) -> io::Result<()> {
+        let temp_path = self.temp_path();
+        let mut file = File::create(&temp_path)?;
+        file.write_all(timestamp.to_string().as_bytes())?;
+        file.sync_all()?;
+        fs::rename(&temp_path, &self.path)?;
+        // Make the rename itself durable
+        #[cfg(unix)]
+        if let Some(dir) = self.path.parent() {
+            let dir = if dir.as_os_str().is_empty() {
+                std::path::Path::new(".")
+            } else {
+                dir
+            };
+            File::open(dir)?.sync_all()?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,183 @@
+//! # Typed
+//!
+//! Typed, prefixed ids like `ord_0AbCdEfGhIj`, so the ids of different entities can not be mixed up.
+//!
+//! Define a kind of ids with `define_id!`, e.g. `define_id!(pub User = "usr")`, which binds the prefix to a generator.
+//! `TypedId<User>` and `TypedId<Order>` are different types, so passing one for the other does not compile.
+
+use std::borrow::Borrow;
+use std::cmp::Ordering;
+use std::fmt::{self, Debug, Display};
+use std::hash::{Hash, Hasher};
+use std::marker::PhantomData;
+use std::str::FromStr;
+
+use crate::{
+    Encoding, GenerateError, IdGenerator, IdInstance, IdRegistry, IdVecInstance, IdWidth,
+    ParseIdError,
+};
+
+/// Kind of ids, binding a prefix to a generator
+pub trait IdKind {
+    /// Prefix of the ids, e.g. `usr`, also the key of the generator in `IdRegistry`
+    const PREFIX: &'static str;
+
+    /// Index of the generator in `IdVecInstance` generating the ids with `TypedId::next`,
+    /// default to none meaning `IdInstance`
+    const VEC_INDEX: Option<usize> = None;
+
+    /// Bit width of the ids of the generator, default to `IdWidth::Bits63`
+    ///
+    /// Strings of ids beyond `i64::MAX` are only parsed with `IdWidth::Bits64`, as the negative ids of all the 64 bits.
+    const ID_WIDTH: IdWidth = IdWidth::Bits63;
+}
+
+/// Define a kind of ids implementing `IdKind`, as an uninhabited marker type
+///
+/// - `define_id!(pub User = "usr")`: `TypedId<User>::next` takes ids from `IdInstance`.
+/// - `define_id!(pub Order = "ord", index = 1)`: `TypedId<Order>::next` takes ids from `IdVecInstance` of index 1.
+/// - `define_id!(pub Event = "evt", width = IdWidth::Bits64)`: the ids are generated with `IdWidth::Bits64`.
+#[macro_export]
+macro_rules! define_id {
+    ($(#[$meta:meta])* $vis:vis $kind:ident = $prefix:expr $(, index = $index:expr)? $(, width = $width:expr)?) => {
+        $(#[$meta])*
+        #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+        $vis enum $kind {}
+
+        impl $crate::IdKind for $kind {
+            const PREFIX: &'static str = $prefix;
+            $(const VEC_INDEX: Option<usize> = Some($index);)?
+            $(const ID_WIDTH: $crate::IdWidth = $width;)?
+        }
+    };
+}
+
+/// Id of a kind, displayed as `{prefix}_{base62 id of 11 characters}`
+///
+/// The base62 id is of fixed width, so the strings of the same kind sort in the order of the ids.
+pub struct TypedId<T: IdKind> {
+    id: i64,
+    kind: PhantomData<fn() -> T>,
+}
+
+impl<T: IdKind> TypedId<T> {
+    /// Wrap a raw id of the kind
+    pub fn new(id: i64) -> Self {
+        TypedId {
+            id,
+            kind: PhantomData,
+        }
+    }
+
+    /// The raw id
+    pub fn id(self) -> i64 {
+        self.id
+    }
+
+    /// Get a unique id from the generator bound to the kind, see `IdKind::VEC_INDEX`
+    pub fn next() -> Result<Self, GenerateError> {
+        let id = match T::VEC_INDEX {
+            Some(index) => IdVecInstance::try_next_id(index)?,
+            None => IdInstance::try_next_id()?,
+        };
+        Ok(TypedId::new(id))
+    }
+
+    /// Get a unique id from the generator registered under the prefix in the registry
+    pub fn next_in<K>(registry: &IdRegistry<K>) -> Result<Self, GenerateError>
+    where
+        K: Borrow<str> + Hash + Eq + Clone + Debug,
+    {
+        Ok(TypedId::new(registry.next_id(T::PREFIX)?))
+    }
+
+    /// Get a unique id from the generator
+    pub fn next_from(generator: &IdGenerator) -> Result<Self, GenerateError> {
+        Ok(TypedId::new(generator.try_next_id()?))
+    }
+}
+
+impl<T: IdKind> Display for TypedId<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}_{}",
+            T::PREFIX,
+            Encoding::Base62.encode_fixed(self.id as u64)
+        )
+    }
+}
+
+impl<T: IdKind> Debug for TypedId<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "TypedId({self})")
+    }
+}
+
+impl<T: IdKind> FromStr for TypedId<T> {
+    type Err = ParseIdError;
+
+    /// Parse strictly the string displayed, rejecting other prefixes and widths,
+    /// and ids beyond `i64::MAX` unless `IdKind::ID_WIDTH` is `IdWidth::Bits64`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (prefix, encoded) = s
+            .rsplit_once('_')
+            .ok_or_else(|| ParseIdError::Invalid(s.to_string()))?;
+        if prefix != T::PREFIX {
+            return Err(ParseIdError::WrongPrefix(
+                T::PREFIX.to_string(),
+                prefix.to_string(),
+            ));
+        }
+        if encoded.len() != Encoding::Base62.fixed_width() {
+            return Err(ParseIdError::Invalid(s.to_string()));
+        }
+        let id = Encoding::Base62.decode(encoded)?;
+        if id > i64::MAX as u64 && T::ID_WIDTH != IdWidth::Bits64 {
+            return Err(ParseIdError::Invalid(s.to_string()));
+        }
+        Ok(TypedId::new(id as i64))
+    }
+}
+
+impl<T: IdKind> From<TypedId<T>> for i64 {
+    fn from(id: TypedId<T>) -> Self {
+        id.id
+    }
+}
+
+// Implemented by hand, so no bound is required on the marker type
+
+impl<T: IdKind> Clone for TypedId<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T: IdKind> Copy for TypedId<T> {}
+
+impl<T: IdKind> PartialEq for TypedId<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.id == other.id
+    }
+}
+
+impl<T: IdKind> Eq for TypedId<T> {}
+
+impl<T: IdKind> PartialOrd for TypedId<T> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<T: IdKind> Ord for TypedId<T> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.id.cmp(&other.id)
+    }
+}
+
+impl<T: IdKind> Hash for TypedId<T> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.id.hash(state)
+    }
+}